@@ -0,0 +1,246 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+#[cfg(feature = "gui")]
+use druid::Data;
+use crate::cell::{CellKind, CellMask};
+use crate::field::HexField;
+use crate::functions;
+use crate::params::SimulationParams;
+use crate::shape::Shape;
+
+/// Thickness of the sponge layer in cells
+const SPONGE_WIDTH: usize = 12;
+/// Reflection coefficient the damping of the sponge layer is tuned for
+const SPONGE_REFLECTION: f64 = 1e-4;
+
+/// Treatment of the wall cells enclosing the simulated shape
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "gui", derive(Data))]
+pub enum BoundaryCondition {
+    /// Walls are held at zero (Dirichlet), waves are fully reflected
+    Reflecting,
+    /// First order Engquist-Majda condition, discretised after Mur
+    Absorbing,
+    /// Graded damping layer (sponge) along the inside of the walls, closed by
+    /// the absorbing condition. It still reflects a little at its inner edge,
+    /// a perfectly matched layer is out of scope since its stretched
+    /// coordinates need layers along the axes and the shapes are arbitrary polygons
+    Sponge,
+}
+
+impl BoundaryCondition {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BoundaryCondition::Reflecting => "Reflecting",
+            BoundaryCondition::Absorbing => "Absorbing",
+            BoundaryCondition::Sponge => "Sponge",
+        }
+    }
+}
+
+/// Parses the name of a boundary condition as used in scenario files
+pub fn parse_boundary_condition(name: &str) -> Option<BoundaryCondition> {
+    match name.trim().to_lowercase().as_str() {
+        "reflecting" => Some(BoundaryCondition::Reflecting),
+        "absorbing" => Some(BoundaryCondition::Absorbing),
+        "sponge" => Some(BoundaryCondition::Sponge),
+        _ => None,
+    }
+}
+
+/// Boundary type of a single edge of the shape file
+#[derive(Clone, Copy, PartialEq)]
+pub enum EdgeType {
+    /// Follows the boundary condition selected in the GUI
+    Default,
+    /// Wall held at zero, reflects with inverted phase
+    Dirichlet,
+    /// Free wall with zero normal derivative, reflects without phase change
+    Neumann,
+    /// Open port, outgoing waves are absorbed
+    Absorbing,
+    /// Edges sharing the same id are glued together, waves leaving through
+    /// one edge enter through the other. Paired edges have to be parallel and
+    /// of equal length.
+    Periodic(usize),
+}
+
+/// Parses the optional boundary type columns of a line of the shape file
+///
+/// A line reads "x1 y1 x2 y2 [type [pair id]]", where type is one of dirichlet,
+/// neumann, absorbing or periodic. Periodic edges need the id of their pair.
+pub fn parse_edge_type(line: &[String]) -> Result<EdgeType, String> {
+    let columns: Vec<&str> = line.iter().skip(4).map(|entry| entry.trim()).filter(|entry| !entry.is_empty()).collect();
+    match columns.first().map(|entry| entry.to_lowercase()).as_deref() {
+        None | Some("default") => Ok(EdgeType::Default),
+        Some("dirichlet") => Ok(EdgeType::Dirichlet),
+        Some("neumann") | Some("free") => Ok(EdgeType::Neumann),
+        Some("absorbing") | Some("open") => Ok(EdgeType::Absorbing),
+        Some("periodic") => match columns.get(1).and_then(|entry| usize::from_str(entry).ok()) {
+            Some(id) => Ok(EdgeType::Periodic(id)),
+            None => Err(String::from("periodic edges need a pair id")),
+        },
+        Some(other) => Err(format!("unknown boundary type {}", other)),
+    }
+}
+
+/// A wall cell together with its neighbours inside the shape
+pub struct Wall {
+    pub i_y: usize,
+    pub i_x: usize,
+    pub inner: Vec<(usize, usize)>,
+    /// Boundary type of the closest edge of the shape
+    pub edge_type: EdgeType,
+    /// Interior cell whose value a periodic wall takes over
+    pub partner: Option<(usize, usize)>,
+}
+
+impl Wall {
+    /// Returns the treatment of the wall, resolving Default with the global condition
+    fn treatment(&self, condition: BoundaryCondition) -> EdgeType {
+        match (self.edge_type, condition) {
+            (EdgeType::Default, BoundaryCondition::Reflecting) => EdgeType::Dirichlet,
+            (EdgeType::Default, _) => EdgeType::Absorbing,
+            (EdgeType::Periodic(_), _) if self.partner.is_none() => EdgeType::Dirichlet,
+            (edge_type, _) => edge_type,
+        }
+    }
+
+    fn inner_mean(&self, hex_field: &HexField) -> f64 {
+        let sum: f64 = self.inner.iter().map(|&(n_y, n_x)| hex_field[n_y][n_x]).sum();
+        sum / self.inner.len() as f64
+    }
+}
+
+/// Returns all wall cells of the hex grid that touch the inside
+pub fn find_walls(hex_grid: &CellMask) -> Vec<Wall> {
+    let mut walls: Vec<Wall> = Vec::new();
+    for i_y in 1..hex_grid.y_len() - 1 {
+        for i_x in 1..hex_grid.x_len() - 1 {
+            if hex_grid[i_y][i_x] == CellKind::Wall {
+                let inner: Vec<(usize, usize)> = functions::neighbour_indices(i_y, i_x).iter()
+                    .cloned()
+                    .filter(|&(n_y, n_x)| hex_grid[n_y][n_x].is_active())
+                    .collect();
+                if !inner.is_empty() {
+                    walls.push(Wall{i_y, i_x, inner, edge_type: EdgeType::Default, partner: None});
+                }
+            }
+        }
+    }
+    walls
+}
+
+/// Assigns every wall cell the boundary type of the closest edge of the shape
+///
+/// - x_off, y_off: range x left and range y down of the grid
+pub fn tag_walls(walls: &mut [Wall], hex_grid: &CellMask, shape: &Shape, x_off: usize, y_off: usize, spacing: f64) {
+    let (x1, y1, x2, y2) = (&shape.x1, &shape.y1, &shape.x2, &shape.y2);
+    let edge_types: &[EdgeType] = &shape.edge_types;
+    for wall in walls.iter_mut() {
+        let (x, y) = functions::get_cord(wall.i_x, wall.i_y, x_off, y_off, spacing);
+        // Finding the closest edge
+        let mut closest: usize = 0;
+        let mut min_dist: f64 = f64::INFINITY;
+        for i in 0..x1.len() {
+            let dist: f64 = functions::segment_distance(x, y, x1[i], y1[i], x2[i], y2[i]);
+            if dist < min_dist {
+                min_dist = dist;
+                closest = i;
+            }
+        }
+        wall.edge_type = edge_types[closest];
+        if let EdgeType::Periodic(id) = wall.edge_type {
+            // Shifting the wall by the distance between the midpoints of the pair
+            let pair: Option<usize> = (0..x1.len()).find(|&i| i != closest && edge_types[i] == EdgeType::Periodic(id));
+            wall.partner = pair.and_then(|i| {
+                let shift_x: f64 = 0.5*(x1[i] + x2[i] - x1[closest] - x2[closest]);
+                let shift_y: f64 = 0.5*(y1[i] + y2[i] - y1[closest] - y2[closest]);
+                functions::get_index(x + shift_x, y + shift_y, x_off, y_off, spacing)
+            }).and_then(|(p_x, p_y)| {
+                // Falling back to an inside neighbour if the target is no inner cell
+                if p_y < hex_grid.y_len() && p_x < hex_grid.x_len() && hex_grid[p_y][p_x].is_active() {
+                    return Some((p_y, p_x))
+                }
+                if p_y == 0 || p_x == 0 || p_y + 1 >= hex_grid.y_len() || p_x + 1 >= hex_grid.x_len() {
+                    return None
+                }
+                functions::neighbour_indices(p_y, p_x).iter().cloned().find(|&(n_y, n_x)| hex_grid[n_y][n_x].is_active())
+            });
+            if wall.partner.is_none() {
+                println!("No periodic partner found for wall cell ({}, {})", wall.i_x, wall.i_y);
+            }
+        }
+    }
+}
+
+/// Returns the damping profile of the sponge layer per unit of wave speed
+///
+/// The damping grows quadratically from zero at SPONGE_WIDTH cells away from the
+/// walls to its maximum on the cells directly next to a wall. Distances are
+/// counted in steps on the hex lattice. Only walls following the global
+/// boundary condition are lined with the layer. The profile is multiplied by
+/// the speed of each cell when stepping, so the layer stays tuned to speed maps
+/// and painted speeds.
+pub fn sponge_profile(hex_grid: &CellMask, walls: &[Wall], spacing: f64) -> HexField {
+    let y_len: usize = hex_grid.y_len();
+    let x_len: usize = hex_grid.x_len();
+    let mut distance: Vec<Vec<usize>> = vec!(vec!(usize::MAX; x_len); y_len);
+    let mut queue: VecDeque<(usize, usize)> = VecDeque::new();
+    // Start the breadth first search at the walls
+    for wall in walls.iter().filter(|wall| wall.edge_type == EdgeType::Default) {
+        distance[wall.i_y][wall.i_x] = 0;
+        queue.push_back((wall.i_y, wall.i_x));
+    }
+    while let Some((i_y, i_x)) = queue.pop_front() {
+        let next: usize = distance[i_y][i_x] + 1;
+        if next > SPONGE_WIDTH {
+            continue;
+        }
+        for &(n_y, n_x) in functions::neighbour_indices(i_y, i_x).iter() {
+            if hex_grid[n_y][n_x].is_active() && distance[n_y][n_x] > next {
+                distance[n_y][n_x] = next;
+                queue.push_back((n_y, n_x));
+            }
+        }
+    }
+    // Quadratic grading, sigma_max chosen for the requested reflection coefficient at unit speed
+    let width: f64 = SPONGE_WIDTH as f64;
+    let sigma_max: f64 = -3.0*SPONGE_REFLECTION.ln() / (2.0*width*spacing);
+    let mut sigma: HexField = HexField::new(x_len, y_len, 0.0);
+    for i_y in 0..y_len {
+        for i_x in 0..x_len {
+            let dist: usize = distance[i_y][i_x];
+            if (1..=SPONGE_WIDTH).contains(&dist) {
+                sigma[i_y][i_x] = sigma_max*((width - dist as f64 + 1.0) / width).powi(2);
+            }
+        }
+    }
+    sigma
+}
+
+/// Sets the values of the wall cells at the new time level
+///
+/// - hex_speed: wave speed per cell
+/// - hex_tn: field at the current time level
+/// - hex_temp: field at the new time level, interior already updated
+pub fn apply_walls(condition: BoundaryCondition, walls: &Vec<Wall>, params: &SimulationParams, hex_speed: &HexField,
+        hex_tn: &HexField, hex_temp: &mut HexField) {
+    for wall in walls {
+        let value: f64 = match wall.treatment(condition) {
+            EdgeType::Neumann => wall.inner_mean(hex_temp),
+            EdgeType::Absorbing => {
+                // Mur coefficient for a wave leaving over one cell distance
+                let courant: f64 = params.courant(wall.inner_mean(hex_speed));
+                let coeff: f64 = (courant - 1.0) / (courant + 1.0);
+                wall.inner_mean(hex_tn) + coeff*(wall.inner_mean(hex_temp) - hex_tn[wall.i_y][wall.i_x])
+            }
+            EdgeType::Periodic(_) => {
+                let (p_y, p_x) = wall.partner.unwrap();
+                hex_temp[p_y][p_x]
+            }
+            EdgeType::Dirichlet | EdgeType::Default => 0.0,
+        };
+        hex_temp[wall.i_y][wall.i_x] = value;
+    }
+}
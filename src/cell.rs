@@ -0,0 +1,32 @@
+#[cfg(feature = "gui")]
+use druid::Data;
+use crate::field::HexField;
+
+/// Category of a cell of the hex grid
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "gui", derive(Data))]
+pub enum CellKind {
+    /// Outside of the shape, never updated
+    Outside,
+    /// Inside of the shape with the default medium
+    Interior,
+    /// Outside cell touching the shape, set by the boundary condition
+    Wall,
+    /// Inside cell driven by a point source
+    Source,
+    /// Inside cell of a region of the speed map, numbered in the order of the file
+    Material(u8),
+}
+
+impl CellKind {
+    /// Returns true for all cells inside the shape, which are updated every time step
+    pub fn is_active(&self) -> bool {
+        match self {
+            CellKind::Outside | CellKind::Wall => false,
+            CellKind::Interior | CellKind::Source | CellKind::Material(_) => true,
+        }
+    }
+}
+
+/// Kind of every cell of the hex grid
+pub type CellMask = HexField<CellKind>;
@@ -0,0 +1,486 @@
+use std::convert::TryInto;
+use std::fs;
+use std::sync::Arc;
+use crate::boundary::{BoundaryCondition, EdgeType};
+use crate::cell::{CellKind, CellMask};
+use crate::field::HexField;
+use crate::params::{SimulationParams, MAX_THREADS};
+use crate::scenario::Pulse;
+use crate::shape::Shape;
+use crate::simulation::{self, SimulationData};
+use crate::source::{Source, SourceKind};
+
+/// First bytes of every checkpoint file
+const MAGIC: &[u8; 8] = b"HEXWAVE\0";
+/// Version of the layout written by `save`, raised on every change of the layout
+pub const VERSION: u32 = 2;
+
+/// Appends little endian values to a byte buffer
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn text(&mut self, text: &str) {
+        self.u32(text.len() as u32);
+        self.bytes.extend_from_slice(text.as_bytes());
+    }
+
+    /// Writes a flag followed by the text if there is one
+    fn optional_text(&mut self, text: &Option<String>) {
+        match text {
+            Some(text) => {
+                self.u8(1);
+                self.text(text);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    /// Writes the cells of a field row by row, without the padding
+    fn field(&mut self, field: &HexField) {
+        for i_y in 0..field.y_len() {
+            for &value in field[i_y].iter() {
+                self.f64(value);
+            }
+        }
+    }
+}
+
+/// Reads little endian values from a byte buffer, fails at its end
+struct Reader {
+    bytes: Vec<u8>,
+    pos: usize,
+}
+
+impl Reader {
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        if self.pos + len > self.bytes.len() {
+            return Err(String::from("the file is truncated"))
+        }
+        let start: usize = self.pos;
+        self.pos += len;
+        Ok(&self.bytes[start..start + len])
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn text(&mut self) -> Result<String, String> {
+        let len: usize = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| String::from("it holds a path that is not UTF-8"))
+    }
+
+    fn optional_text(&mut self) -> Result<Option<String>, String> {
+        match self.u8()? {
+            0 => Ok(None),
+            _ => Ok(Some(self.text()?)),
+        }
+    }
+
+    fn field(&mut self, x_len: usize, y_len: usize) -> Result<HexField, String> {
+        let mut field: HexField = HexField::new(x_len, y_len, 0.0);
+        for i_y in 0..y_len {
+            for i_x in 0..x_len {
+                field[i_y][i_x] = self.f64()?;
+            }
+        }
+        Ok(field)
+    }
+}
+
+/// Codes of the cell kinds, the id of a material follows in a second byte
+fn cell_code(cell: CellKind) -> (u8, u8) {
+    match cell {
+        CellKind::Outside => (0, 0),
+        CellKind::Interior => (1, 0),
+        CellKind::Wall => (2, 0),
+        CellKind::Source => (3, 0),
+        CellKind::Material(id) => (5, id),
+    }
+}
+
+fn cell_from_code(code: u8, id: u8) -> Result<CellKind, String> {
+    match code {
+        0 => Ok(CellKind::Outside),
+        1 => Ok(CellKind::Interior),
+        2 => Ok(CellKind::Wall),
+        3 => Ok(CellKind::Source),
+        5 => Ok(CellKind::Material(id)),
+        other => Err(format!("unknown cell kind {}", other)),
+    }
+}
+
+/// Codes of the edge types, the pair id of a periodic edge follows
+fn edge_code(edge_type: EdgeType) -> (u8, u32) {
+    match edge_type {
+        EdgeType::Default => (0, 0),
+        EdgeType::Dirichlet => (1, 0),
+        EdgeType::Neumann => (2, 0),
+        EdgeType::Absorbing => (3, 0),
+        EdgeType::Periodic(id) => (4, id as u32),
+    }
+}
+
+fn edge_from_code(code: u8, id: u32) -> Result<EdgeType, String> {
+    match code {
+        0 => Ok(EdgeType::Default),
+        1 => Ok(EdgeType::Dirichlet),
+        2 => Ok(EdgeType::Neumann),
+        3 => Ok(EdgeType::Absorbing),
+        4 => Ok(EdgeType::Periodic(id as usize)),
+        other => Err(format!("unknown edge type {}", other)),
+    }
+}
+
+fn boundary_code(boundary: BoundaryCondition) -> u8 {
+    match boundary {
+        BoundaryCondition::Reflecting => 0,
+        BoundaryCondition::Absorbing => 1,
+        BoundaryCondition::Sponge => 2,
+    }
+}
+
+fn boundary_from_code(code: u8) -> Result<BoundaryCondition, String> {
+    match code {
+        0 => Ok(BoundaryCondition::Reflecting),
+        1 => Ok(BoundaryCondition::Absorbing),
+        2 => Ok(BoundaryCondition::Sponge),
+        other => Err(format!("unknown boundary condition {}", other)),
+    }
+}
+
+fn source_code(kind: SourceKind) -> u8 {
+    match kind {
+        SourceKind::Sine => 0,
+        SourceKind::Ricker => 1,
+        SourceKind::Chirp => 2,
+        SourceKind::PulseTrain => 3,
+    }
+}
+
+fn source_from_code(code: u8) -> Result<SourceKind, String> {
+    match code {
+        0 => Ok(SourceKind::Sine),
+        1 => Ok(SourceKind::Ricker),
+        2 => Ok(SourceKind::Chirp),
+        3 => Ok(SourceKind::PulseTrain),
+        other => Err(format!("unknown source kind {}", other)),
+    }
+}
+
+/// Writes the complete state of a simulation to a binary checkpoint
+///
+/// The file holds the shape, parameters, boundary condition, cell mask,
+/// speed and damping maps, sources, both time levels and the step index, all
+/// numbers little endian. Loading it continues the run bit for bit.
+pub fn save(path: &str, sim: &SimulationData) -> Result<(), String> {
+    fs::write(path, checkpoint_bytes(sim)).map_err(|error| format!("Could not write {}: {}", path, error))
+}
+
+/// Returns the contents of the checkpoint file of a simulation, see `save`
+fn checkpoint_bytes(sim: &SimulationData) -> Vec<u8> {
+    let mut writer = Writer{bytes: Vec::new()};
+    writer.bytes.extend_from_slice(MAGIC);
+    writer.u32(VERSION);
+    // Shape with the paths of its maps, which are only kept for scenario files
+    let shape: &Shape = &sim.shape;
+    writer.text(&shape.path);
+    writer.optional_text(&shape.speed_path);
+    writer.optional_text(&shape.damping_path);
+    writer.u32(shape.x1.len() as u32);
+    for i in 0..shape.x1.len() {
+        writer.f64(shape.x1[i]);
+        writer.f64(shape.y1[i]);
+        writer.f64(shape.x2[i]);
+        writer.f64(shape.y2[i]);
+        let (code, id) = edge_code(shape.edge_types[i]);
+        writer.u8(code);
+        writer.u32(id);
+    }
+    // Parameters and settings of the run
+    writer.f64(sim.params.speed);
+    writer.f64(sim.params.spacing);
+    writer.f64(sim.params.time_spacing);
+    writer.u32(sim.params.threads as u32);
+    writer.u8(boundary_code(sim.boundary));
+    writer.f64(sim.damping);
+    writer.u32(sim.n);
+    writer.u32(sim.n_max);
+    // Sizes of the sections that follow, which fix the length of the file
+    let x_len: usize = sim.hex_grid.x_len();
+    let y_len: usize = sim.hex_grid.y_len();
+    writer.u32(x_len as u32);
+    writer.u32(y_len as u32);
+    writer.u32(sim.sources.len() as u32);
+    writer.u32(sim.pulses.len() as u32);
+    // Grid mask and medium
+    for i_y in 0..y_len {
+        for &cell in sim.hex_grid[i_y].iter() {
+            let (code, id) = cell_code(cell);
+            writer.u8(code);
+            writer.u8(id);
+        }
+    }
+    writer.field(&sim.hex_speed);
+    writer.field(&sim.hex_damp);
+    // Sources and hand made displacements
+    for source in sim.sources.iter() {
+        writer.u8(source_code(source.kind));
+        writer.u32(source.i_x as u32);
+        writer.u32(source.i_y as u32);
+        writer.u8(source.active as u8);
+        writer.f64(source.amplitude);
+        writer.f64(source.frequency);
+        writer.f64(source.phase);
+        writer.f64(source.sweep);
+        writer.f64(source.duty);
+        let (code, id) = cell_code(source.cell);
+        writer.u8(code);
+        writer.u8(id);
+    }
+    for pulse in sim.pulses.iter() {
+        let (code, x, y, width, height) = match *pulse {
+            Pulse::Point{x, y, height} => (0, x, y, 0.0, height),
+            Pulse::Gauss{x, y, width, height} => (1, x, y, width, height),
+        };
+        writer.u8(code);
+        writer.f64(x);
+        writer.f64(y);
+        writer.f64(width);
+        writer.f64(height);
+    }
+    // Both time levels of the leapfrog scheme
+    let levels = sim.levels.lock();
+    writer.field(levels.previous());
+    writer.field(levels.current());
+    writer.bytes
+}
+
+/// Reads a checkpoint written by `save` and restores the simulation
+///
+/// A file that is truncated, corrupt or of another version is an error
+/// naming the file, nothing is restored from it.
+pub fn load(path: &str, cmap: Arc<[[u8; 3]; 256]>) -> Result<SimulationData, String> {
+    let bytes: Vec<u8> = fs::read(path).map_err(|error| format!("Could not read {}: {}", path, error))?;
+    restore(bytes, cmap).map_err(|error| format!("Could not load {}: {}", path, error))
+}
+
+/// Bytes of a cell past the header: its kind, the speed and damping maps and both time levels
+const CELL_BYTES: u64 = 2 + 4*8;
+/// Bytes of a source: kind, cell indices, active flag, five numbers and the kind of its cell
+const SOURCE_BYTES: u64 = 1 + 2*4 + 1 + 5*8 + 2;
+/// Bytes of a pulse: kind and four numbers
+const PULSE_BYTES: u64 = 1 + 4*8;
+
+/// Restores a simulation from the contents of a checkpoint file
+fn restore(bytes: Vec<u8>, cmap: Arc<[[u8; 3]; 256]>) -> Result<SimulationData, String> {
+    let mut reader = Reader{bytes, pos: 0};
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(String::from("not a checkpoint file"))
+    }
+    let version: u32 = reader.u32()?;
+    if version != VERSION {
+        return Err(format!("version {} is not supported, expected version {}", version, VERSION))
+    }
+    // Setting up the shape
+    let mut shape = Shape {
+        path: reader.text()?,
+        x1: Vec::new(),
+        y1: Vec::new(),
+        x2: Vec::new(),
+        y2: Vec::new(),
+        edge_types: Vec::new(),
+        speed_path: reader.optional_text()?,
+        damping_path: reader.optional_text()?,
+    };
+    let edges: u32 = reader.u32()?;
+    for _ in 0..edges {
+        shape.x1.push(reader.f64()?);
+        shape.y1.push(reader.f64()?);
+        shape.x2.push(reader.f64()?);
+        shape.y2.push(reader.f64()?);
+        let code: u8 = reader.u8()?;
+        let id: u32 = reader.u32()?;
+        shape.edge_types.push(edge_from_code(code, id)?);
+    }
+    let params = SimulationParams {
+        speed: reader.f64()?,
+        spacing: reader.f64()?,
+        time_spacing: reader.f64()?,
+        threads: reader.u32()? as usize,
+    };
+    if params.threads > MAX_THREADS {
+        return Err(format!("it asks for {} threads, at most {} are allowed", params.threads, MAX_THREADS))
+    }
+    let boundary: BoundaryCondition = boundary_from_code(reader.u8()?)?;
+    let damping: f64 = reader.f64()?;
+    let n: u32 = reader.u32()?;
+    let n_max: u32 = reader.u32()?;
+    let x_len: usize = reader.u32()? as usize;
+    let y_len: usize = reader.u32()? as usize;
+    let source_count: u32 = reader.u32()?;
+    let pulse_count: u32 = reader.u32()?;
+    // The header fixes the length of the rest, so a corrupt size is refused before anything is set up
+    let expected: u64 = (x_len as u64*y_len as u64)*CELL_BYTES + source_count as u64*SOURCE_BYTES + pulse_count as u64*PULSE_BYTES;
+    let remaining: u64 = (reader.bytes.len() - reader.pos) as u64;
+    if remaining != expected {
+        return Err(format!("it holds {} bytes after its header, expected {}", remaining, expected))
+    }
+    if shape.x1.is_empty() || params.spacing <= 0.0 || !params.spacing.is_finite() {
+        return Err(String::from("it holds no shape"))
+    }
+    let (shape_x_len, shape_y_len, _x_off, _y_off) = simulation::grid_layout(&shape, params.spacing);
+    if x_len != shape_x_len || y_len != shape_y_len {
+        return Err(format!("the grid of {}x{} cells does not match its shape", x_len, y_len))
+    }
+    // The grid, walls and sponge layer are set up from the shape again, the
+    // maps are read from the checkpoint instead of their files
+    let mut bare_shape: Shape = shape.clone();
+    bare_shape.speed_path = None;
+    bare_shape.damping_path = None;
+    let mut sim: SimulationData = SimulationData::new(Arc::new(bare_shape), params, cmap);
+    sim.shape = Arc::new(shape);
+    sim.params = params;
+    sim.boundary = boundary;
+    sim.damping = damping;
+    sim.n = n;
+    sim.n_max = n_max;
+    let mut hex_grid: CellMask = HexField::new(x_len, y_len, CellKind::Outside);
+    for i_y in 0..y_len {
+        for i_x in 0..x_len {
+            let code: u8 = reader.u8()?;
+            let id: u8 = reader.u8()?;
+            hex_grid[i_y][i_x] = cell_from_code(code, id)?;
+        }
+    }
+    sim.hex_grid = Arc::new(hex_grid);
+    sim.hex_speed = Arc::new(reader.field(x_len, y_len)?);
+    sim.hex_damp = Arc::new(reader.field(x_len, y_len)?);
+    sim.update_max_speed();
+    // Sources are restored as saved, their cells are already marked in the mask
+    let mut sources: Vec<Source> = Vec::new();
+    for _ in 0..source_count {
+        let kind: SourceKind = source_from_code(reader.u8()?)?;
+        let i_x: usize = reader.u32()? as usize;
+        let i_y: usize = reader.u32()? as usize;
+        if i_x >= x_len || i_y >= y_len {
+            return Err(String::from("it holds a source outside of the grid"))
+        }
+        let active: bool = reader.u8()? != 0;
+        let (amplitude, frequency) = (reader.f64()?, reader.f64()?);
+        let mut source: Source = Source::new(kind, i_x, i_y, amplitude, frequency)?;
+        source.active = active;
+        source.phase = reader.f64()?;
+        source.sweep = reader.f64()?;
+        source.duty = reader.f64()?;
+        let code: u8 = reader.u8()?;
+        let id: u8 = reader.u8()?;
+        source.cell = cell_from_code(code, id)?;
+        sources.push(source);
+    }
+    sim.sources = Arc::new(sources);
+    let mut pulses: Vec<Pulse> = Vec::new();
+    for _ in 0..pulse_count {
+        let code: u8 = reader.u8()?;
+        let (x, y, width, height) = (reader.f64()?, reader.f64()?, reader.f64()?, reader.f64()?);
+        pulses.push(match code {
+            0 => Pulse::Point{x, y, height},
+            1 => Pulse::Gauss{x, y, width, height},
+            other => return Err(format!("unknown pulse kind {}", other)),
+        });
+    }
+    sim.pulses = Arc::new(pulses);
+    // Time levels
+    {
+        let mut levels = sim.levels.lock();
+        *levels.previous_mut() = reader.field(x_len, y_len)?;
+        *levels.current_mut() = reader.field(x_len, y_len)?;
+    }
+    Ok(sim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a square with a source and a displacement, stepped for a while
+    fn running() -> SimulationData {
+        let shape: Shape = Shape::square(EdgeType::Default);
+        let params = SimulationParams{speed: 1.0, spacing: 0.01, time_spacing: 0.005, threads: 1};
+        let mut sim: SimulationData = SimulationData::new(Arc::new(shape), params, Arc::new([[0; 3]; 256]));
+        sim.add_source(0.3, 0.6, SourceKind::Ricker, 1.0, 5.0);
+        let (x_centre, y_centre) = (sim.hex_grid.x_len()/2, sim.hex_grid.y_len()/2);
+        sim.add_gauss(x_centre, y_centre, 0.05, 1.0);
+        for _ in 0..20 {
+            sim.calc_next_frame();
+        }
+        sim
+    }
+
+    #[test]
+    fn resumes_bit_for_bit() {
+        let mut sim: SimulationData = running();
+        let path: String = std::env::temp_dir().join("wave_resumes_bit_for_bit.wave").to_string_lossy().into_owned();
+        save(&path, &sim).unwrap();
+        let mut resumed: SimulationData = load(&path, sim.cmap.clone()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(checkpoint_bytes(&resumed) == checkpoint_bytes(&sim));
+        for _ in 0..30 {
+            sim.calc_next_frame();
+            resumed.calc_next_frame();
+        }
+        assert_eq!(resumed.n, 50);
+        assert!(checkpoint_bytes(&resumed) == checkpoint_bytes(&sim));
+    }
+
+    #[test]
+    fn refuses_truncated_and_corrupt_files() {
+        let sim: SimulationData = running();
+        let bytes: Vec<u8> = checkpoint_bytes(&sim);
+        for &len in [0, 10, 40, bytes.len()/2, bytes.len() - 1].iter() {
+            assert!(restore(bytes[..len].to_vec(), sim.cmap.clone()).is_err(), "{} of {} bytes restored", len, bytes.len());
+        }
+        // Magic, version, the shape path, both map paths, the edges and the parameters precede the boundary condition
+        let boundary_pos: usize = 8 + 4 + 4 + "square".len() + 2 + 4 + 4*(4*8 + 5) + 3*8 + 4;
+        assert!(bytes[boundary_pos] == boundary_code(sim.boundary));
+        let mut corrupt: Vec<u8> = bytes.clone();
+        corrupt[boundary_pos] = 9;
+        match restore(corrupt, sim.cmap.clone()) {
+            Err(error) => assert_eq!(error, "unknown boundary condition 9"),
+            Ok(_) => panic!("Restored an unknown boundary condition"),
+        }
+        // The threads follow the three numbers of the parameters
+        let mut corrupt: Vec<u8> = bytes.clone();
+        corrupt[boundary_pos - 4..boundary_pos].copy_from_slice(&u32::MAX.to_le_bytes());
+        match restore(corrupt, sim.cmap.clone()) {
+            Err(error) => assert_eq!(error, format!("it asks for {} threads, at most {} are allowed", u32::MAX, MAX_THREADS)),
+            Ok(_) => panic!("Restored billions of threads"),
+        }
+        let mut longer: Vec<u8> = bytes.clone();
+        longer.push(0);
+        assert!(restore(longer, sim.cmap.clone()).is_err());
+        assert!(restore(bytes, sim.cmap.clone()).is_ok());
+    }
+}
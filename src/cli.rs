@@ -0,0 +1,348 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use crate::checkpoint;
+use crate::export::{self, FieldSeries};
+use crate::functions;
+use crate::modes::Modes;
+use crate::recording::Recorder;
+use crate::scenario::{Output, Scenario};
+use crate::simulation::SimulationData;
+use crate::spectrum::{self, Window};
+
+const USAGE: &str = "Usage:
+    wave                                    start the application
+    wave run <input> [options]              run a scenario, print its progress and write its outputs
+    wave render <input> <png> [options]     run a scenario and save the last frame as PNG
+    wave export <input> <file> [options]    run a scenario and save the last field as CSV, or NumPy for .npy files
+    wave modes <input> [<png>] [options]    print the frequencies of the lowest eigenmodes and save their shapes as numbered PNG images
+
+The input is a scenario file or a checkpoint ending in .wave, which continues the saved run.
+
+Options:
+    --steps N              number of time steps to run, by default up to the step limit of the input
+    --checkpoint <file>    save the state after the last step to a checkpoint
+    --scale N              draw PNG images with hexagons N pixels across instead of one pixel per cell
+    --smooth               interpolate between the cell centres in images drawn with a scale above 1
+    --record <file>        record the run to a GIF, or to numbered PNG images for any other extension
+    --series <file>        save the field of every recorded time step to one CSV or .npy file
+    --every N              record only every Nth time step
+    --probes <file>        save the values recorded by the probes of the scenario as CSV
+    --energy <file>        save the energy and norms of the field of every recorded time step as CSV
+    --spectrum <file>      save the amplitude spectra of the probes as CSV and print their peaks
+    --window <name>        window of the spectra: rectangular, hann (default), hamming or blackman
+    --count N              number of eigenmodes to compute, 6 by default
+    --mode K               start the run from eigenmode K at its largest displacement, 0 is the lowest";
+
+/// Runs a command given on the command line without opening a window
+///
+/// Returns the exit code, 0 on success, 1 on wrong arguments or a diverged run
+pub fn run(args: &[String], cmap_path: &str) -> i32 {
+    // Separating the options from the positional arguments
+    let mut positional: Vec<&str> = Vec::new();
+    let mut steps: Option<u32> = None;
+    let mut checkpoint_path: Option<&str> = None;
+    let mut scale: usize = 1;
+    let mut smooth: bool = false;
+    let mut record_path: Option<&str> = None;
+    let mut series_path: Option<&str> = None;
+    let mut probes_path: Option<&str> = None;
+    let mut spectrum_path: Option<&str> = None;
+    let mut energy_path: Option<&str> = None;
+    let mut window: Window = Window::Hann;
+    let mut every: u32 = 1;
+    let mut count: usize = 6;
+    let mut mode: Option<usize> = None;
+    let mut i: usize = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--steps" => {
+                i += 1;
+                steps = args.get(i).and_then(|arg| u32::from_str(arg).ok());
+                if steps.is_none() {
+                    eprintln!("--steps needs a number of time steps");
+                    return 1
+                }
+            }
+            "--checkpoint" => {
+                i += 1;
+                checkpoint_path = args.get(i).map(|arg| arg.as_str());
+                if checkpoint_path.is_none() {
+                    eprintln!("--checkpoint needs a file to save to");
+                    return 1
+                }
+            }
+            "--scale" => {
+                i += 1;
+                match args.get(i).and_then(|arg| usize::from_str(arg).ok()) {
+                    Some(value) if value > 0 => scale = value,
+                    _ => {
+                        eprintln!("--scale needs a positive number of pixels");
+                        return 1
+                    }
+                }
+            }
+            "--smooth" => smooth = true,
+            "--record" => {
+                i += 1;
+                record_path = args.get(i).map(|arg| arg.as_str());
+                if record_path.is_none() {
+                    eprintln!("--record needs a file to record to");
+                    return 1
+                }
+            }
+            "--series" => {
+                i += 1;
+                series_path = args.get(i).map(|arg| arg.as_str());
+                if series_path.is_none() {
+                    eprintln!("--series needs a file to save to");
+                    return 1
+                }
+            }
+            "--probes" => {
+                i += 1;
+                probes_path = args.get(i).map(|arg| arg.as_str());
+                if probes_path.is_none() {
+                    eprintln!("--probes needs a file to save to");
+                    return 1
+                }
+            }
+            "--spectrum" => {
+                i += 1;
+                spectrum_path = args.get(i).map(|arg| arg.as_str());
+                if spectrum_path.is_none() {
+                    eprintln!("--spectrum needs a file to save to");
+                    return 1
+                }
+            }
+            "--energy" => {
+                i += 1;
+                energy_path = args.get(i).map(|arg| arg.as_str());
+                if energy_path.is_none() {
+                    eprintln!("--energy needs a file to save to");
+                    return 1
+                }
+            }
+            "--window" => {
+                i += 1;
+                match args.get(i).map(|name| (name, spectrum::parse_window(name))) {
+                    Some((_name, Some(parsed))) => window = parsed,
+                    Some((name, None)) => {
+                        eprintln!("Unknown window {}, expected rectangular, hann, hamming or blackman", name);
+                        return 1
+                    }
+                    None => {
+                        eprintln!("--window needs the name of a window");
+                        return 1
+                    }
+                }
+            }
+            "--every" => {
+                i += 1;
+                match args.get(i).and_then(|arg| u32::from_str(arg).ok()) {
+                    Some(value) if value > 0 => every = value,
+                    _ => {
+                        eprintln!("--every needs a positive number of time steps");
+                        return 1
+                    }
+                }
+            }
+            "--count" => {
+                i += 1;
+                match args.get(i).and_then(|arg| usize::from_str(arg).ok()) {
+                    Some(value) if value > 0 => count = value,
+                    _ => {
+                        eprintln!("--count needs a positive number of modes");
+                        return 1
+                    }
+                }
+            }
+            "--mode" => {
+                i += 1;
+                mode = args.get(i).and_then(|arg| usize::from_str(arg).ok());
+                if mode.is_none() {
+                    eprintln!("--mode needs the number of a mode");
+                    return 1
+                }
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return 0
+            }
+            arg => positional.push(arg),
+        }
+        i += 1;
+    }
+    let output: Option<&str> = match (positional.first().copied(), positional.len()) {
+        (Some("run"), 2) | (Some("modes"), 2) => None,
+        (Some("render"), 3) | (Some("export"), 3) | (Some("modes"), 3) => Some(positional[2]),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 1
+        }
+    };
+
+    // Setting up the simulation from a scenario or continuing a checkpoint
+    let mut scenario_output: Output = Output::default();
+    let mut sim: SimulationData = if positional[1].ends_with(".wave") {
+        match checkpoint::load(positional[1], Arc::new(functions::get_cmap(cmap_path))) {
+            Ok(sim) => sim,
+            Err(error) => {
+                eprintln!("{}", error);
+                return 1
+            }
+        }
+    } else {
+        let scenario: Scenario = match Scenario::load(positional[1]) {
+            Ok(scenario) => scenario,
+            Err(error) => {
+                eprintln!("{}", error);
+                return 1
+            }
+        };
+        let cmap_path: String = scenario.colormap.clone().unwrap_or(String::from(cmap_path));
+        scenario_output = scenario.output.clone();
+        match scenario.build(Arc::new(functions::get_cmap(&cmap_path))) {
+            Ok(sim) => sim,
+            Err(error) => {
+                eprintln!("Could not build scenario {}: {}", positional[1], error);
+                return 1
+            }
+        }
+    };
+    // Solving for the eigenmodes instead of stepping
+    if positional[0] == "modes" {
+        let modes: Modes = sim.modes(count);
+        for (k, frequency) in modes.frequencies.iter().enumerate() {
+            println!("Mode {}: frequency {:.6}", k, frequency);
+        }
+        if let Some(path) = output {
+            if failed(export::write_modes(path, &sim, &modes, scale, smooth)) {
+                return 1
+            }
+        }
+        return 0
+    }
+    if let Some(k) = mode {
+        let modes: Modes = sim.modes(count.max(k + 1));
+        match (modes.shapes.get(k), modes.frequencies.get(k)) {
+            (Some(shape), Some(&frequency)) => {
+                println!("Starting from mode {} with frequency {:.6}", k, frequency);
+                sim.start_from_mode(shape, frequency, 1.0);
+            }
+            _ => {
+                eprintln!("The shape has only {} modes", modes.shapes.len());
+                return 1
+            }
+        }
+    }
+    let last: u32 = match steps {
+        Some(steps) => sim.n + steps,
+        None => sim.n_max,
+    };
+    // Recording starts with the initial frame
+    let mut recorder: Option<Recorder> = record_path.map(|path| Recorder::new(path, every, scale, smooth));
+    let mut series: Option<FieldSeries> = None;
+    if let Some(path) = series_path {
+        match FieldSeries::new(path, every, &sim) {
+            Ok(opened) => series = Some(opened),
+            Err(error) => {
+                eprintln!("{}", error);
+                return 1
+            }
+        }
+    }
+    // The energy costs a pass over the grid, it is only measured when it is saved
+    if energy_path.is_some() || scenario_output.energy.is_some() {
+        sim.energy_every = every;
+    }
+    // Stepping with a progress line every tenth of the run
+    let report: u32 = ((last.saturating_sub(sim.n))/10).max(1);
+    loop {
+        if let Some(recorder) = &mut recorder {
+            if failed(recorder.record(&sim)) {
+                return 1
+            }
+        }
+        if let Some(series) = &mut series {
+            if failed(series.record(&sim)) {
+                return 1
+            }
+        }
+        if sim.n >= last || sim.diverged {
+            break;
+        }
+        sim.calc_next_frame();
+        if sim.n.is_multiple_of(report) {
+            let energy: String = match sim.energy.samples.last() {
+                Some(sample) => format!(", energy {:.6e}", sample.total()),
+                None => String::new(),
+            };
+            println!("Step {}/{}, max amplitude {:.4e}{}", sim.n, last, functions::get_max_abs(sim.levels.lock().current()), energy);
+        }
+    }
+    if let (Some(recorder), Some(path)) = (recorder, record_path) {
+        println!("Recorded {} frames to {}", recorder.finish(), path);
+    }
+    if let (Some(series), Some(path)) = (series, series_path) {
+        match series.finish() {
+            Ok(frames) => println!("Saved {} fields to {}", frames, path),
+            Err(error) => {
+                eprintln!("{}", error);
+                return 1
+            }
+        }
+    }
+    // The energy is saved even for a diverged run, it shows how the blow up built up
+    if let Some(path) = energy_path {
+        if failed(export::write_energy(path, &sim)) {
+            return 1
+        }
+    }
+    if sim.diverged {
+        eprintln!("Simulation diverged at step {}", sim.n);
+        return 1
+    }
+
+    // Writing the outputs of the scenario and the ones given on the command line
+    let mut results: Vec<Result<(), String>> = Vec::new();
+    if let Some(path) = &scenario_output.png {
+        results.push(export::write_png(path, &sim, scale, smooth));
+    }
+    if let Some(path) = &scenario_output.csv {
+        results.push(export::write_field(path, &sim));
+    }
+    if let Some(path) = &scenario_output.probes {
+        results.push(export::write_probes(path, &sim));
+    }
+    if let Some(path) = &scenario_output.energy {
+        results.push(export::write_energy(path, &sim));
+    }
+    if let Some(path) = checkpoint_path {
+        results.push(checkpoint::save(path, &sim));
+    }
+    if let Some(path) = probes_path {
+        results.push(export::write_probes(path, &sim));
+    }
+    if let Some(path) = spectrum_path {
+        results.push(export::write_spectra(path, &sim, window));
+    }
+    match (positional[0], output) {
+        ("render", Some(path)) => results.push(export::write_png(path, &sim, scale, smooth)),
+        ("export", Some(path)) => results.push(export::write_field(path, &sim)),
+        _ => {}
+    }
+    // Every output is attempted, a failed one still fails the run
+    let failures: usize = results.into_iter().map(failed).filter(|&failure| failure).count();
+    if failures > 0 {1} else {0}
+}
+
+/// Prints the error of a failed output and returns true if there was one
+fn failed(result: Result<(), String>) -> bool {
+    match result {
+        Ok(()) => false,
+        Err(error) => {
+            eprintln!("{}", error);
+            true
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use crate::cell::CellMask;
+use crate::field::HexField;
+use crate::functions;
+use crate::params::SimulationParams;
+use crate::probe::MAX_SAMPLES;
+
+/// Energy and norms of the field after one time step
+#[derive(Clone, Copy)]
+pub struct Diagnostics {
+    pub time: f64,
+    /// Kinetic energy between the previous and the current level
+    pub kinetic: f64,
+    /// Potential energy between the previous and the current level, see measure
+    pub potential: f64,
+    /// Root of the integral of the squared field
+    pub l2_norm: f64,
+    /// Largest magnitude of the field
+    pub max_norm: f64,
+}
+
+impl Diagnostics {
+    pub fn total(&self) -> f64 {
+        self.kinetic + self.potential
+    }
+}
+
+/// Returns the energy and norms of the field inside the shape
+///
+/// The energy is the one the leapfrog scheme of calc_next_frame conserves
+/// exactly when there is no damping, no source and every wall is held at zero,
+/// so any drift comes from the boundary treatment, damping, sources or a blow up:
+///
+/// E = 1/2 sum (u_new - u_old)^2/(c^2 dt^2) + 1/2 sum u_new*(-laplace(u_old))
+///
+/// summed over the cells times their area. It belongs half a time step before
+/// the current level. The potential part mixes two levels and can turn
+/// slightly negative for waves near the grid resolution.
+pub fn measure(previous: &HexField, current: &HexField, hex_grid: &CellMask, hex_speed: &HexField,
+        params: &SimulationParams, pool: &ThreadPool, time: f64) -> Diagnostics {
+    let spacing: f64 = params.spacing;
+    let dt: f64 = params.time_spacing;
+    let area: f64 = SimulationParams::SI60*spacing*spacing;
+    // Sums of the kinetic, potential and squared terms with the largest magnitude, row by row in parallel
+    let (kinetic, potential, square, max_norm) = pool.install(|| {
+        (0..hex_grid.y_len()).into_par_iter().map(|i_y| {
+            let mut sums: (f64, f64, f64, f64) = (0.0, 0.0, 0.0, 0.0);
+            for i_x in 0..hex_grid.x_len() {
+                if hex_grid[i_y][i_x].is_active() {
+                    let value: f64 = current[i_y][i_x];
+                    let velocity: f64 = (value - previous[i_y][i_x])/dt;
+                    let neighbours: f64 = functions::neighbour_indices(i_y, i_x).iter().map(|&(n_y, n_x)| previous[n_y][n_x]).sum();
+                    let laplace: f64 = 2.0/3.0*(neighbours - 6.0*previous[i_y][i_x])/(spacing*spacing);
+                    sums.0 += velocity*velocity/hex_speed[i_y][i_x].powi(2);
+                    sums.1 -= value*laplace;
+                    sums.2 += value*value;
+                    sums.3 = sums.3.max(value.abs());
+                }
+            }
+            sums
+        }).reduce(|| (0.0, 0.0, 0.0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2, a.3.max(b.3)))
+    });
+    Diagnostics {
+        time,
+        kinetic: 0.5*area*kinetic,
+        potential: 0.5*area*potential,
+        l2_norm: (area*square).sqrt(),
+        max_norm,
+    }
+}
+
+/// Diagnostics of the time steps measured since the simulation was set up
+#[derive(Clone)]
+pub struct EnergySeries {
+    pub samples: Vec<Diagnostics>,
+    /// Smallest and largest total, kinetic and potential energy recorded, zero included
+    pub min: f64,
+    pub max: f64,
+    /// Keeps every nth of the recorded samples
+    stride: usize,
+    /// Number of samples recorded, kept or not
+    recorded: usize,
+}
+
+impl EnergySeries {
+    pub fn new() -> EnergySeries {
+        EnergySeries{samples: Vec::new(), min: 0.0, max: 0.0, stride: 1, recorded: 0}
+    }
+
+    /// Appends a sample
+    ///
+    /// Once MAX_SAMPLES are kept every second one is dropped and only every
+    /// second sample is kept from then on, so a long run stays covered from its
+    /// start at an even spacing.
+    pub fn record(&mut self, sample: Diagnostics) {
+        if self.recorded.is_multiple_of(self.stride) {
+            for &value in [sample.total(), sample.kinetic, sample.potential].iter() {
+                self.min = self.min.min(value);
+                self.max = self.max.max(value);
+            }
+            self.samples.push(sample);
+            if self.samples.len() >= MAX_SAMPLES {
+                let thinned: Vec<Diagnostics> = self.samples.iter().step_by(2).cloned().collect();
+                self.samples = thinned;
+                self.stride *= 2;
+            }
+        }
+        self.recorded += 1;
+    }
+
+    /// Returns the change of the total energy since the first sample holding
+    /// energy, relative to that sample
+    pub fn drift(&self) -> Option<f64> {
+        let first: f64 = self.samples.iter().map(|sample| sample.total()).find(|&total| total != 0.0)?;
+        let last: f64 = self.samples.last()?.total();
+        Some((last - first)/first.abs())
+    }
+
+    /// Writes the series as CSV with the columns time, kinetic, potential,
+    /// total, l2_norm and max_norm
+    pub fn write_csv(&self, path: &str) -> Result<(), String> {
+        let error = |error: std::io::Error| format!("Could not write {}: {}", path, error);
+        let mut writer = BufWriter::new(File::create(path).map_err(error)?);
+        writeln!(writer, "time,kinetic,potential,total,l2_norm,max_norm").map_err(error)?;
+        for sample in self.samples.iter() {
+            writeln!(writer, "{},{},{},{},{},{}", sample.time, sample.kinetic, sample.potential, sample.total(),
+                sample.l2_norm, sample.max_norm).map_err(error)?;
+        }
+        writer.flush().map_err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use super::*;
+    use crate::boundary::EdgeType;
+    use crate::shape::Shape;
+    use crate::simulation::SimulationData;
+
+    #[test]
+    fn conserves_the_energy_between_fixed_walls() {
+        let shape: Shape = Shape::square(EdgeType::Dirichlet);
+        let params = SimulationParams{speed: 1.0, spacing: 0.01, time_spacing: 0.005, threads: 1};
+        let mut sim: SimulationData = SimulationData::new(Arc::new(shape), params, Arc::new([[0; 3]; 256]));
+        sim.energy_every = 1;
+        let (x_centre, y_centre) = (sim.hex_grid.x_len()/2, sim.hex_grid.y_len()/2);
+        sim.add_gauss(x_centre + 3, y_centre - 2, 0.04, 1.0);
+        // Long enough for the pulse to be reflected by the walls several times
+        for _ in 0..400 {
+            sim.calc_next_frame();
+        }
+        let samples: &Vec<Diagnostics> = &sim.energy.samples;
+        assert_eq!(samples.len(), 400);
+        let first: f64 = samples[0].total();
+        assert!(first > 0.0);
+        for sample in samples.iter() {
+            assert!((sample.total() - first).abs() < 1e-10*first, "{} at {} instead of {}", sample.total(), sample.time, first);
+        }
+        assert!(samples.iter().any(|sample| sample.kinetic > 0.1*first) && samples.iter().any(|sample| sample.potential > 0.1*first));
+    }
+
+    #[test]
+    fn thins_a_long_series_evenly() {
+        let mut series: EnergySeries = EnergySeries::new();
+        for k in 0..3*MAX_SAMPLES {
+            let energy: f64 = (k%7) as f64 - 2.0;
+            series.record(Diagnostics{time: k as f64, kinetic: energy, potential: 0.0, l2_norm: 0.0, max_norm: 0.0});
+        }
+        assert!(series.samples.len() < MAX_SAMPLES);
+        assert_eq!(series.samples[0].time, 0.0);
+        let spacing: f64 = series.samples[1].time;
+        assert_eq!(spacing, 4.0);
+        assert!(series.samples.windows(2).all(|pair| pair[1].time - pair[0].time == spacing));
+        assert_eq!((series.min, series.max), (-2.0, 4.0));
+    }
+}
@@ -0,0 +1,235 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use crate::field::HexField;
+use crate::functions::{self, Shading};
+use crate::modes::Modes;
+use crate::simulation::SimulationData;
+use crate::spectrum::{self, Spectrum, Window};
+
+/// Returns the RGB image of the current field with its width and height
+///
+/// Values of -max_val and max_val map to the ends of the colour map. A scale
+/// of 1 gives one pixel per cell, larger scales draw the picture of the window
+/// with every cell as a hexagon of that many pixels across, smooth shading
+/// interpolates between the cell centres like "Smooth" in the window.
+pub fn frame_image(sim: &SimulationData, max_val: f64, scale: usize, smooth: bool) -> (Vec<u8>, usize, usize) {
+    return render(sim.levels.lock().current(), sim, max_val, scale, smooth)
+}
+
+/// Returns the RGB image of any field on the grid of a simulation, see frame_image
+pub fn render(field: &HexField, sim: &SimulationData, max_val: f64, scale: usize, smooth: bool) -> (Vec<u8>, usize, usize) {
+    if scale > 1 {
+        let (picture_width, picture_height) = functions::picture_size(field.x_len(), field.y_len());
+        let width: usize = (picture_width*scale as f64).ceil() as usize;
+        let height: usize = (picture_height*scale as f64).ceil() as usize;
+        let shading: Shading = Shading{max_val, smooth, wall_colour: None};
+        return (functions::picture(field, &sim.hex_grid, &sim.cmap, width, height, &shading), width, height)
+    }
+    (functions::field_image(field, &sim.hex_grid, &sim.cmap, max_val), field.x_len(), field.y_len())
+}
+
+/// Writes the current field as a PNG image scaled to its largest magnitude,
+/// see frame_image for the scale and the shading
+pub fn write_png(path: &str, sim: &SimulationData, scale: usize, smooth: bool) -> Result<(), String> {
+    let max_val: f64 = functions::get_max_abs(sim.levels.lock().current());
+    let (image_vec, width, height) = frame_image(sim, max_val, scale, smooth);
+    save_rgb(path, &image_vec, width, height)
+}
+
+/// Writes the shapes of eigenmodes as numbered PNG images, "modes.png" gives
+/// "modes_00.png" for the lowest mode, "modes_01.png" for the next and so on
+pub fn write_modes(path: &str, sim: &SimulationData, modes: &Modes, scale: usize, smooth: bool) -> Result<(), String> {
+    let stem: &str = path.strip_suffix(".png").unwrap_or(path);
+    for (k, shape) in modes.shapes.iter().enumerate() {
+        let (image_vec, width, height) = render(shape, sim, 1.0, scale, smooth);
+        save_rgb(&format!("{}_{:02}.png", stem, k), &image_vec, width, height)?;
+    }
+    Ok(())
+}
+
+/// Writes an RGB image to a file, the format follows from its extension
+pub fn save_rgb(path: &str, image_vec: &[u8], width: usize, height: usize) -> Result<(), String> {
+    image::save_buffer(path, image_vec, width as u32, height as u32, image::ColorType::Rgb8)
+        .map_err(|error| format!("Could not write {}: {}", path, error))
+}
+
+/// Size of the header of the .npy files written here, the NumPy format
+/// requires a multiple of 64 bytes
+const NPY_HEADER_LEN: usize = 128;
+
+/// Returns the coordinates of all cells inside the shape with their indices, in
+/// the order the fields are written
+fn active_cells(sim: &SimulationData) -> Vec<(usize, usize, f64, f64)> {
+    let mut cells: Vec<(usize, usize, f64, f64)> = Vec::new();
+    for i_y in 0..sim.hex_grid.y_len() {
+        for i_x in 0..sim.hex_grid.x_len() {
+            if sim.hex_grid[i_y][i_x].is_active() {
+                let (x, y) = functions::get_cord(i_x, i_y, sim.x_off, sim.y_off, sim.params.spacing);
+                cells.push((i_x, i_y, x, y));
+            }
+        }
+    }
+    cells
+}
+
+/// Returns the header of a .npy file holding little endian doubles of the given shape
+fn npy_header(shape: &[usize]) -> Vec<u8> {
+    let dims: Vec<String> = shape.iter().map(|dim| dim.to_string()).collect();
+    let mut header: Vec<u8> = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend_from_slice(&((NPY_HEADER_LEN - 10) as u16).to_le_bytes());
+    let mut dict: String = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': ({}), }}", dims.join(", "));
+    // Padding with spaces up to the newline that ends the header
+    while dict.len() < NPY_HEADER_LEN - 11 {
+        dict.push(' ');
+    }
+    dict.push('\n');
+    header.extend_from_slice(dict.as_bytes());
+    header
+}
+
+fn create(path: &str) -> Result<BufWriter<File>, String> {
+    let file: File = File::create(path).map_err(|error| write_error(path, error))?;
+    Ok(BufWriter::new(file))
+}
+
+fn write_error(path: &str, error: std::io::Error) -> String {
+    format!("Could not write {}: {}", path, error)
+}
+
+/// Writes the current field with the physical coordinates of its cells, one
+/// entry per cell inside the shape
+///
+/// Paths ending in .npy give a NumPy array of shape (cells, 3), any other path
+/// gives CSV, both with the columns x, y and value.
+pub fn write_field(path: &str, sim: &SimulationData) -> Result<(), String> {
+    let mut writer = create(path)?;
+    let cells: Vec<(usize, usize, f64, f64)> = active_cells(sim);
+    let levels = sim.levels.lock();
+    let field: &HexField = levels.current();
+    let error = |error: std::io::Error| write_error(path, error);
+    if path.to_lowercase().ends_with(".npy") {
+        writer.write_all(&npy_header(&[cells.len(), 3])).map_err(error)?;
+        for &(i_x, i_y, x, y) in cells.iter() {
+            for value in [x, y, field[i_y][i_x]].iter() {
+                writer.write_all(&value.to_le_bytes()).map_err(error)?;
+            }
+        }
+    } else {
+        writeln!(writer, "x,y,value").map_err(error)?;
+        for &(i_x, i_y, x, y) in cells.iter() {
+            writeln!(writer, "{},{},{}", x, y, field[i_y][i_x]).map_err(error)?;
+        }
+    }
+    writer.flush().map_err(error)
+}
+
+/// Writes the field of a run at a fixed cadence into a single file
+///
+/// Every entry gets the time of its frame in front of the columns of
+/// write_field, CSV gets the columns time, x, y and value, NumPy files an
+/// array of shape (frames, cells, 4). The cells are fixed when the series
+/// starts.
+pub struct FieldSeries {
+    path: String,
+    npy: bool,
+    /// Only every nth offered frame is written
+    every: u32,
+    /// Frames offered so far, written or not
+    offered: u32,
+    /// Frames written so far
+    pub frames: u32,
+    cells: Vec<(usize, usize, f64, f64)>,
+    writer: BufWriter<File>,
+}
+
+impl FieldSeries {
+    pub fn new(path: &str, every: u32, sim: &SimulationData) -> Result<FieldSeries, String> {
+        let npy: bool = path.to_lowercase().ends_with(".npy");
+        let cells: Vec<(usize, usize, f64, f64)> = active_cells(sim);
+        let mut writer = create(path)?;
+        // The number of frames is filled into the header when the series is finished
+        if npy {
+            writer.write_all(&npy_header(&[0, cells.len(), 4])).map_err(|error| write_error(path, error))?;
+        } else {
+            writeln!(writer, "time,x,y,value").map_err(|error| write_error(path, error))?;
+        }
+        Ok(FieldSeries {
+            path: String::from(path),
+            npy,
+            every: every.max(1),
+            offered: 0,
+            frames: 0,
+            cells,
+            writer,
+        })
+    }
+
+    /// Offers the current field of a simulation, called once per time step
+    pub fn record(&mut self, sim: &SimulationData) -> Result<(), String> {
+        self.offered += 1;
+        if !(self.offered - 1).is_multiple_of(self.every) {
+            return Ok(())
+        }
+        let time: f64 = sim.n as f64*sim.params.time_spacing;
+        let levels = sim.levels.lock();
+        let field: &HexField = levels.current();
+        let (writer, path) = (&mut self.writer, &self.path);
+        for &(i_x, i_y, x, y) in self.cells.iter() {
+            let written = if self.npy {
+                [time, x, y, field[i_y][i_x]].iter().try_for_each(|value| writer.write_all(&value.to_le_bytes()))
+            } else {
+                writeln!(writer, "{},{},{},{}", time, x, y, field[i_y][i_x])
+            };
+            written.map_err(|error| write_error(path, error))?;
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Closes the file and returns the number of frames written
+    pub fn finish(mut self) -> Result<u32, String> {
+        if self.npy {
+            let header: Vec<u8> = npy_header(&[self.frames as usize, self.cells.len(), 4]);
+            self.writer.seek(SeekFrom::Start(0)).and_then(|_| self.writer.write_all(&header))
+                .map_err(|error| write_error(&self.path, error))?;
+        }
+        self.writer.flush().map_err(|error| write_error(&self.path, error))?;
+        Ok(self.frames)
+    }
+}
+
+/// Writes the values recorded by the probes as CSV, see ProbeSeries::write_csv
+pub fn write_probes(path: &str, sim: &SimulationData) -> Result<(), String> {
+    sim.probe_series.write_csv(path, &sim.probe_coordinates())
+}
+
+/// Writes the energy and norms of every time step as CSV, see EnergySeries::write_csv
+pub fn write_energy(path: &str, sim: &SimulationData) -> Result<(), String> {
+    sim.energy.write_csv(path)
+}
+
+/// Writes the amplitude spectra of all probes as CSV with a frequency column
+/// and one column per probe, and prints their strongest peaks
+pub fn write_spectra(path: &str, sim: &SimulationData, window: Window) -> Result<(), String> {
+    let series = &sim.probe_series;
+    let spectra: Vec<Spectrum> = (0..series.values.len()).map(|probe| series.spectrum(probe, window, usize::MAX)).collect();
+    let mut writer = create(path)?;
+    let error = |error: std::io::Error| write_error(path, error);
+    let mut header: String = String::from("frequency");
+    for (k, (x, y)) in sim.probe_coordinates().iter().enumerate() {
+        header.push_str(&format!(",\"|U({}, {})|\"", x, y));
+        let peaks: Vec<String> = spectrum::find_peaks(&spectra[k], 5).iter().map(|(frequency, _amplitude)| format!("{:.4}", frequency)).collect();
+        println!("Peaks of the probe at {} {} with {} window: {}", x, y, window.name().to_lowercase(), peaks.join(", "));
+    }
+    writeln!(writer, "{}", header).map_err(error)?;
+    if let Some(first) = spectra.first() {
+        for (i, frequency) in first.frequencies.iter().enumerate() {
+            let mut line: String = frequency.to_string();
+            for spectrum in spectra.iter() {
+                line.push_str(&format!(",{}", spectrum.amplitudes[i]));
+            }
+            writeln!(writer, "{}", line).map_err(error)?;
+        }
+    }
+    writer.flush().map_err(error)
+}
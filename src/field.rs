@@ -0,0 +1,200 @@
+use std::ops::{Index, IndexMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+#[cfg(feature = "gui")]
+use druid::Data;
+use rayon::prelude::*;
+
+/// Cells of padding around every field, neighbours of the outermost cells can
+/// be read without bound checks
+pub const PADDING: usize = 1;
+
+/// Values on the hex grid, stored row after row in one flat buffer
+///
+/// Indexing with a row number returns the row without its padding, so cells are
+/// accessed as field[i_y][i_x] like on the nested vectors used before. Cells hold
+/// f64 unless another type is given.
+#[derive(Clone)]
+pub struct HexField<T = f64> {
+    x_len: usize,
+    y_len: usize,
+    /// Distance between vertically adjacent cells in the buffer
+    stride: usize,
+    data: Vec<T>,
+}
+
+impl<T: Copy> HexField<T> {
+    /// Returns a field of the given size with every cell, padding included, set to a value
+    pub fn new(x_len: usize, y_len: usize, value: T) -> HexField<T> {
+        let stride: usize = x_len + 2*PADDING;
+        HexField {
+            x_len,
+            y_len,
+            stride,
+            data: vec!(value; stride*(y_len + 2*PADDING)),
+        }
+    }
+
+    pub fn x_len(&self) -> usize {
+        self.x_len
+    }
+
+    pub fn y_len(&self) -> usize {
+        self.y_len
+    }
+
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns the position of a cell in the flat buffer
+    pub fn flat_index(&self, i_x: usize, i_y: usize) -> usize {
+        (i_y + PADDING)*self.stride + i_x + PADDING
+    }
+
+    /// Returns the flat buffer including the padding
+    pub fn data(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Sets every cell to the same value, the padding included
+    pub fn fill(&mut self, value: T) {
+        self.data.iter_mut().for_each(|entry| *entry = value);
+    }
+
+    /// Copies every cell of a field of the same size without allocating
+    pub fn copy_from(&mut self, other: &HexField<T>) {
+        self.data.copy_from_slice(&other.data);
+    }
+
+}
+
+impl<T: Copy + Send> HexField<T> {
+    /// Returns a parallel iterator over the rows and their numbers, padding excluded
+    pub fn par_rows_mut(&mut self) -> impl IndexedParallelIterator<Item = (usize, &mut [T])> {
+        let x_len: usize = self.x_len;
+        self.data.par_chunks_mut(self.stride)
+            .skip(PADDING)
+            .take(self.y_len)
+            .enumerate()
+            .map(move |(i_y, row)| (i_y, &mut row[PADDING..PADDING + x_len]))
+    }
+}
+
+impl<T: Copy> Index<usize> for HexField<T> {
+    type Output = [T];
+
+    fn index(&self, i_y: usize) -> &[T] {
+        let start: usize = self.flat_index(0, i_y);
+        &self.data[start..start + self.x_len]
+    }
+}
+
+impl<T: Copy> IndexMut<usize> for HexField<T> {
+    fn index_mut(&mut self, i_y: usize) -> &mut [T] {
+        let start: usize = self.flat_index(0, i_y);
+        &mut self.data[start..start + self.x_len]
+    }
+}
+
+/// The three time levels of the leapfrog scheme in a ring buffer
+///
+/// A time step writes the next level into the buffer of the oldest one and
+/// advances the ring, no field is copied or allocated.
+#[derive(Clone)]
+pub struct FieldLevels {
+    levels: [HexField; 3],
+    current: usize,
+}
+
+impl FieldLevels {
+    /// Returns three levels of the given size at rest
+    pub fn new(x_len: usize, y_len: usize) -> FieldLevels {
+        let field: HexField = HexField::new(x_len, y_len, 0.0);
+        FieldLevels {
+            levels: [field.clone(), field.clone(), field],
+            current: 0,
+        }
+    }
+
+    /// Returns the field at the current time level
+    pub fn current(&self) -> &HexField {
+        &self.levels[self.current]
+    }
+
+    pub fn current_mut(&mut self) -> &mut HexField {
+        &mut self.levels[self.current]
+    }
+
+    /// Returns the field one time step before the current level
+    pub fn previous(&self) -> &HexField {
+        &self.levels[(self.current + 2)%3]
+    }
+
+    pub fn previous_mut(&mut self) -> &mut HexField {
+        &mut self.levels[(self.current + 2)%3]
+    }
+
+    /// Returns the previous and current level to read from and the next level to write to
+    pub fn step_mut(&mut self) -> (&HexField, &HexField, &mut HexField) {
+        let [first, second, third] = &mut self.levels;
+        match self.current {
+            0 => (third, first, second),
+            1 => (first, second, third),
+            _ => (second, third, first),
+        }
+    }
+
+    /// Makes the next level the current one
+    pub fn advance(&mut self) {
+        self.current = (self.current + 1)%3;
+    }
+
+    /// Copies the previous and current level of levels of the same size into
+    /// these, the next level is written by the next time step anyway
+    pub fn copy_from(&mut self, other: &FieldLevels) {
+        self.current = other.current;
+        self.previous_mut().copy_from(other.previous());
+        self.current_mut().copy_from(other.current());
+    }
+
+    /// Returns true if both hold fields of the same size
+    pub fn same_size(&self, other: &FieldLevels) -> bool {
+        self.current().x_len() == other.current().x_len() && self.current().y_len() == other.current().y_len()
+    }
+}
+
+/// Time levels shared by all copies of a simulation
+///
+/// Druid keeps copies of the app data to compare against, so the levels are
+/// mutated in place behind a lock instead of being copied on write. Copies
+/// count as the same, changes are noticed through the step and edit counters
+/// of the simulation.
+#[derive(Clone)]
+pub struct SharedLevels(Arc<Mutex<FieldLevels>>);
+
+impl SharedLevels {
+    pub fn new(levels: FieldLevels) -> SharedLevels {
+        SharedLevels(Arc::new(Mutex::new(levels)))
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, FieldLevels> {
+        self.0.lock().expect("Field levels poisoned")
+    }
+
+    /// Returns a copy of the levels that is not shared with these
+    pub fn detached(&self) -> SharedLevels {
+        SharedLevels::new(self.lock().clone())
+    }
+
+    /// Returns true if no other copy of the simulation holds these levels
+    pub fn is_unique(&self) -> bool {
+        Arc::strong_count(&self.0) == 1
+    }
+}
+
+#[cfg(feature = "gui")]
+impl Data for SharedLevels {
+    fn same(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
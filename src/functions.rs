@@ -1,194 +1,372 @@
-use std::sync::Arc;
-use std::str::FromStr;
-
-const SPACING: f64 = 0.01;
-const SI60: f64 = 0.8660254037844386;
-const CO60: f64 = 0.5;
-
-/// Returns x and y coordinates if given indices and the offsets
-/// 
-/// range x left and range y down
-pub fn get_cord(i_x: usize, i_y: usize, x_off: usize, y_off: usize) -> (f64, f64) {
-    if i_y%2 == 0 {
-        return ((i_x as f64 - x_off as f64) * SPACING, (i_y as f64 - y_off as f64) * SPACING * SI60);
-    } else {
-        return ((i_x as f64 - x_off as f64) * SPACING + CO60 * SPACING, (i_y as f64 - y_off as f64) * SPACING * SI60);
-    }
-}
-
-pub fn get_slope(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
-    return (y2 - y1)/(x2 - x1)
-}
-
-pub fn get_offset(x1: f64, y1: f64, m: f64) -> f64 {
-    return y1 - m*x1
-}
-
-/// Returns the coordinates of the interception of two linear functions:
-/// 
-/// f1(x)=ax+b
-/// 
-/// f2(x)=cx+d
-/// 
-/// where f2 has a slope of zero, and therefore c = 0.0
-pub fn get_inter(x1: f64, y1: f64, x2: f64, y2: f64, offset: f64) -> (f64, f64) {
-    let a: f64 = get_slope(x1, y1, x2, y2);
-    let c: f64 = 0.0;
-
-    let b: f64 = get_offset(x1, y1, a);
-    let d: f64 = offset;
-
-    let x_res: f64 = (d - b)/(a- c);
-    let y_res: f64 = a*x_res + b;
-
-    return (x_res, y_res)
-}
-
-/// Returns a sorted list of all intersections on a horizontal line with a
-/// certain offset
-/// 
-/// - y in the tuple is unused, but could be used to make it more secure
-pub fn get_vec_intersect(y_offset: f64, x1: &Vec<f64>, y1: &Vec<f64>, x2: &Vec<f64>, y2: &Vec<f64>) -> Vec<f64> {
-    let mut res: Vec<f64> = Vec::new();
-    
-    for i in 0..x1.len() {
-        let (x, _y) = get_inter(x1[i], y1[i], x2[i], y2[i], y_offset);
-        if ((x1[i] <= x) && (x <= x2[i])) || ((x2[i] <= x) && (x <= x1[i])) {
-            res.push(x);
-        }
-    }
-
-    res.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    return res
-}
-
-pub fn max_element_f64(vec_1: &Vec<f64>, vec_2: &Vec<f64>) -> f64 {
-    let max_v1: f64 = vec_1.iter().cloned().fold(0./0., f64::max);
-    let max_v2: f64 = vec_2.iter().cloned().fold(0./0., f64::max);
-    let max: f64 ;
-    
-    if max_v1 < max_v2 {
-        max = max_v1;
-    } else {
-        max = max_v2;
-    }
-    return max
-}
-
-pub fn min_element_f64(vec_1: &Vec<f64>, vec_2: &Vec<f64>) -> f64 {
-    let min_v1: f64 = vec_1.iter().cloned().fold(0./0., f64::min);
-    let min_v2: f64 = vec_2.iter().cloned().fold(0./0., f64::min);
-    let min: f64 ;
-    
-    if min_v1 < min_v2 {
-        min = min_v1;
-    } else {
-        min = min_v2;
-    }
-    return min
-}
-
-/// Returns the amount of elements in a Vec that are bigger than the input value
-pub fn amount_bigger(value: f64, vector: &Vec<f64>) -> usize {
-    let mut count: usize = 0;
-    for entry in vector {
-        if value < *entry {
-            count += 1;
-        }
-    }
-    return count
-}
-
-/// Returns the sum of the neighbour entries of the hex grid
-pub fn neighbour_sum(hex_grid: &Vec<Vec<f64>>, i: usize, j:usize) -> f64 {
-    let sum: f64;
-    if i%2 == 0 {
-        sum = hex_grid[i-1][j-1] + hex_grid[i][j-1] + hex_grid[i-1][j] + hex_grid[i+1][j] + hex_grid[i-1][j+1] + hex_grid[i][j+1];
-    } else {
-        sum = hex_grid[i][j-1] + hex_grid[i+1][j-1] + hex_grid[i-1][j] + hex_grid[i+1][j] + hex_grid[i][j+1] + hex_grid[i+1][j+1];
-    }
-    return sum
-}
-
-/// Determines color depending on the value and a cmap
-pub fn determine_color(value: f64, cmap: &[[u8; 3]; 256], base_level: f64, range: f64) -> [u8; 3] {
-    let norm_value: f64 = (value - base_level) / range + 0.5;
-    let mut index: usize = (norm_value*255.0).round() as usize;
-    if index > 255 {index = 255}
-    return [cmap[index][0], cmap[index][1], cmap[index][2]]
-}
-
-/// Parses CSV into a string vector
-pub fn csv_parse(path: &str, delimiter: char) -> Vec<Vec<String>> {
-    // Setting up error message
-    let mut error_message: String = String::from_str("Something went wrong reading the following file: ").expect("Could not parse");
-    error_message.push_str(path);
-    // Loading file with data
-    let content = std::fs::read_to_string(path).expect(&error_message);
-    // Setting up string vectors to fill
-    let mut data_array: Vec<Vec<String>> = Vec::new();
-    let mut string_array: Vec<String> = Vec::new();
-    let mut string: String = "".to_string();
-    // Filling vectors with data
-    for character in content.chars() {
-        if character == delimiter {
-            string_array.push(string.clone());
-            string = "".to_string();
-        } else if character == '\n' {
-            string_array.push(string.clone());
-            string = "".to_string();
-            data_array.push(string_array.clone());
-            string_array = Vec::new();
-        } else {
-            string.push(character)
-        }
-    }
-    return data_array
-}
-
-/// Returns the colormap used for visualisation
-pub fn get_cmap(cmap_path: &str) -> [[u8; 3]; 256] {
-    // Loading file with cmap data
-    let data_array: Vec<Vec<String>> = csv_parse(cmap_path, ',');
-    // Setting up u8 array to fill
-    let mut rgb_array: [[u8; 3]; 256] = [[0; 3]; 256];
-    // Filling array with string data
-    for i in 0..data_array.len() {
-        rgb_array[i][0] = (f64::from_str(&data_array[i][1]).unwrap()*255.0).round() as u8;
-        rgb_array[i][1] = (f64::from_str(&data_array[i][2]).unwrap()*255.0).round() as u8;
-        rgb_array[i][2] = (f64::from_str(&data_array[i][3]).unwrap()*255.0).round() as u8;
-    }
-    return rgb_array
-}
-
-/// Returns the maximum absolute value of an Arc<Vec<Vec<f64>>>
-pub fn get_max_abs(obj: &Arc<Vec<Vec<f64>>>) -> f64 {
-    let len_outer: usize = obj.len();
-    let len_inner: usize = obj[0].len();
-    let mut current_max: f64 = 0.0;
-    
-    for i1 in 0..len_outer {
-        for i2 in 0..len_inner{
-            if obj[i1][i2].abs() > current_max {
-                current_max = obj[i1][i2].abs();
-            }
-        }
-    }
-    current_max
-}
-
-/// Returns the value of a Gaussian
-/// 
-/// - height: peak height
-/// - stdv: standard deviation
-/// - x: deviation from the peak
-pub fn gaussian(height: f64, stdv: f64, x: f64) -> f64 {
-    height*(-1.0*x.powi(2) / (2.0*stdv.powi(2))).exp()
-}
-
-/// Gives the distance of two points on the grid
-pub fn grid_distance(i_x_1: usize, i_y_1: usize, i_x_2: usize, i_y_2: usize, x_off: usize, y_off: usize) -> f64 {
-    let (x_1, y_1) = get_cord(i_x_1, i_y_1, x_off, y_off);
-    let (x_2, y_2) = get_cord(i_x_2, i_y_2, x_off, y_off);
-    ((x_1 - x_2).powi(2) + (y_1 - y_2).powi(2)).sqrt()
+use std::str::FromStr;
+use crate::cell::{CellKind, CellMask};
+use crate::field::HexField;
+use crate::params::SimulationParams;
+
+/// Largest stable Courant number c*dt/h of the leapfrog scheme on the hex stencil
+///
+/// The most negative eigenvalue of the discrete Laplacian is -6/h^2, stability
+/// requires (c*dt/h)^2 * 6 <= 4
+pub const CFL_LIMIT: f64 = 0.816496580927726;
+
+/// Field magnitude above which the simulation is considered to have diverged
+pub const DIVERGENCE_LIMIT: f64 = 1e8;
+
+/// Returns x and y coordinates if given indices, the offsets and the spacing
+/// 
+/// range x left and range y down
+pub fn get_cord(i_x: usize, i_y: usize, x_off: usize, y_off: usize, spacing: f64) -> (f64, f64) {
+    if i_y.is_multiple_of(2) {
+        ((i_x as f64 - x_off as f64) * spacing, (i_y as f64 - y_off as f64) * spacing * SimulationParams::SI60)
+    } else {
+        ((i_x as f64 - x_off as f64) * spacing + SimulationParams::CO60 * spacing, (i_y as f64 - y_off as f64) * spacing * SimulationParams::SI60)
+    }
+}
+
+/// Returns the indices of the grid cell closest to the given coordinates
+///
+/// Inverse of get_cord, returns None if the point lies before the first row or
+/// column of the grid
+pub fn get_index(x: f64, y: f64, x_off: usize, y_off: usize, spacing: f64) -> Option<(usize, usize)> {
+    let i_y: f64 = (y / (spacing*SimulationParams::SI60)).round() + y_off as f64;
+    if i_y < 0.0 {
+        return None
+    }
+    let mut i_x: f64 = x / spacing + x_off as f64;
+    if (i_y as usize)%2 == 1 {
+        i_x -= SimulationParams::CO60;
+    }
+    let i_x: f64 = i_x.round();
+    if i_x < 0.0 {
+        return None
+    }
+    Some((i_x as usize, i_y as usize))
+}
+
+/// Returns the distance of a point to the line segment between two vertices
+pub fn segment_distance(x: f64, y: f64, x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
+    let len_sq: f64 = (x2 - x1).powi(2) + (y2 - y1).powi(2);
+    let mut t: f64 = 0.0;
+    if len_sq > 0.0 {
+        t = (((x - x1)*(x2 - x1) + (y - y1)*(y2 - y1)) / len_sq).clamp(0.0, 1.0);
+    }
+    ((x - x1 - t*(x2 - x1)).powi(2) + (y - y1 - t*(y2 - y1)).powi(2)).sqrt()
+}
+
+pub fn get_slope(x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
+    (y2 - y1)/(x2 - x1)
+}
+
+pub fn get_offset(x1: f64, y1: f64, m: f64) -> f64 {
+    y1 - m*x1
+}
+
+/// Returns the coordinates of the interception of two linear functions:
+/// 
+/// f1(x)=ax+b
+/// 
+/// f2(x)=cx+d
+/// 
+/// where f2 has a slope of zero, and therefore c = 0.0
+pub fn get_inter(x1: f64, y1: f64, x2: f64, y2: f64, offset: f64) -> (f64, f64) {
+    // Vertical edges have no finite slope
+    if x1 == x2 {
+        return (x1, offset)
+    }
+    let a: f64 = get_slope(x1, y1, x2, y2);
+    let c: f64 = 0.0;
+
+    let b: f64 = get_offset(x1, y1, a);
+    let d: f64 = offset;
+
+    let x_res: f64 = (d - b)/(a- c);
+    let y_res: f64 = a*x_res + b;
+
+    (x_res, y_res)
+}
+
+/// Returns a sorted list of all intersections on a horizontal line with a
+/// certain offset
+/// 
+/// - y in the tuple is unused, but could be used to make it more secure
+pub fn get_vec_intersect(y_offset: f64, x1: &[f64], y1: &[f64], x2: &[f64], y2: &[f64]) -> Vec<f64> {
+    let mut res: Vec<f64> = Vec::new();
+    
+    for i in 0..x1.len() {
+        let (x, _y) = get_inter(x1[i], y1[i], x2[i], y2[i], y_offset);
+        let in_y: bool = ((y1[i] <= y_offset) && (y_offset <= y2[i])) || ((y2[i] <= y_offset) && (y_offset <= y1[i]));
+        if in_y && (((x1[i] <= x) && (x <= x2[i])) || ((x2[i] <= x) && (x <= x1[i]))) {
+            res.push(x);
+        }
+    }
+
+    res.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    res
+}
+
+pub fn max_element_f64(vec_1: &[f64], vec_2: &[f64]) -> f64 {
+    let max_v1: f64 = vec_1.iter().cloned().fold(f64::NAN, f64::max);
+    let max_v2: f64 = vec_2.iter().cloned().fold(f64::NAN, f64::max);
+    
+    if max_v1 < max_v2 {
+        max_v1
+    } else {
+        max_v2
+    }
+}
+
+pub fn min_element_f64(vec_1: &[f64], vec_2: &[f64]) -> f64 {
+    let min_v1: f64 = vec_1.iter().cloned().fold(f64::NAN, f64::min);
+    let min_v2: f64 = vec_2.iter().cloned().fold(f64::NAN, f64::min);
+    
+    if min_v1 < min_v2 {
+        min_v1
+    } else {
+        min_v2
+    }
+}
+
+/// Returns the amount of elements in a Vec that are bigger than the input value
+pub fn amount_bigger(value: f64, vector: &[f64]) -> usize {
+    let mut count: usize = 0;
+    for entry in vector {
+        if value < *entry {
+            count += 1;
+        }
+    }
+    count
+}
+
+/// Returns the indices (y, x) of the six neighbours of a cell on the hex grid
+///
+/// Odd rows are shifted by half a cell to the right, see get_cord
+pub fn neighbour_indices(i_y: usize, i_x: usize) -> [(usize, usize); 6] {
+    if i_y.is_multiple_of(2) {
+        [(i_y-1, i_x-1), (i_y-1, i_x), (i_y, i_x-1), (i_y, i_x+1), (i_y+1, i_x-1), (i_y+1, i_x)]
+    } else {
+        [(i_y-1, i_x), (i_y-1, i_x+1), (i_y, i_x-1), (i_y, i_x+1), (i_y+1, i_x), (i_y+1, i_x+1)]
+    }
+}
+
+/// Determines color depending on the value and a cmap
+pub fn determine_color(value: f64, cmap: &[[u8; 3]; 256], base_level: f64, range: f64) -> [u8; 3] {
+    let norm_value: f64 = (value - base_level) / range + 0.5;
+    let mut index: usize = (norm_value*255.0).round() as usize;
+    if index > 255 {index = 255}
+    [cmap[index][0], cmap[index][1], cmap[index][2]]
+}
+
+/// Returns the RGB image of a field with one pixel per cell, values of
+/// -max_val and max_val map to the ends of the colour map. Cells outside of
+/// the shape stay black.
+pub fn field_image(field: &HexField, hex_grid: &CellMask, cmap: &[[u8; 3]; 256], max_val: f64) -> Vec<u8> {
+    let xr: usize = field.x_len();
+    let yr: usize = field.y_len();
+    let mut image_vec: Vec<u8> = vec!(0; xr * yr * 3);
+    for i_y in 0..yr {
+        for i_x in 0..xr {
+            if hex_grid[i_y][i_x] != CellKind::Outside {
+                let cols = determine_color(field[i_y][i_x], cmap, 0.0, 2.0*max_val);
+                image_vec[i_y*xr*3 + i_x*3] = cols[0];
+                image_vec[i_y*xr*3 + i_x*3 + 1] = cols[1];
+                image_vec[i_y*xr*3 + i_x*3 + 2] = cols[2];
+            }
+        }
+    }
+    image_vec
+}
+
+/// Returns the cell with the centre closest to a point given in cell spacings
+/// from the centre of the first cell, points beyond the grid give a cell at its edge
+pub fn closest_cell(x: f64, y: f64, x_len: usize, y_len: usize) -> (usize, usize) {
+    // The closest centre lies in one of the two rows around the point
+    let row: f64 = (y/SimulationParams::SI60).floor();
+    let mut closest: (usize, usize) = (0, 0);
+    let mut closest_dist: f64 = f64::INFINITY;
+    for i_y in [row, row + 1.0].iter().map(|&i_y| i_y.max(0.0).min((y_len - 1) as f64) as usize) {
+        let shift: f64 = SimulationParams::CO60*(i_y%2) as f64;
+        let i_x: f64 = (x - shift).round().max(0.0).min((x_len - 1) as f64);
+        let dist: f64 = (x - i_x - shift).powi(2) + (y - i_y as f64*SimulationParams::SI60).powi(2);
+        if dist < closest_dist {
+            closest_dist = dist;
+            closest = (i_x as usize, i_y);
+        }
+    }
+    closest
+}
+
+/// Returns the width and height of the picture of a grid in cell spacings
+///
+/// The picture reaches half a spacing beyond the outer cell centres, the odd
+/// rows are shifted right by half a spacing and the rows lie sqrt(3)/2 apart.
+pub fn picture_size(x_len: usize, y_len: usize) -> (f64, f64) {
+    (x_len as f64 + 0.5, y_len as f64*SimulationParams::SI60)
+}
+
+/// Returns the centre of a cell in the picture of a grid as fractions of its width and height
+pub fn picture_position(i_x: usize, i_y: usize, x_len: usize, y_len: usize) -> (f64, f64) {
+    let (width, height) = picture_size(x_len, y_len);
+    let x: f64 = i_x as f64 + 0.5 + SimulationParams::CO60*(i_y%2) as f64;
+    let y: f64 = (i_y as f64 + 0.5)*SimulationParams::SI60;
+    (x/width, y/height)
+}
+
+/// Returns the cell whose hexagon covers a point of the picture of a grid,
+/// given as fractions of its width and height
+pub fn picture_cell(x_frac: f64, y_frac: f64, x_len: usize, y_len: usize) -> (usize, usize) {
+    let (width, height) = picture_size(x_len, y_len);
+    closest_cell(x_frac*width - 0.5, y_frac*height - 0.5*SimulationParams::SI60, x_len, y_len)
+}
+
+/// Returns the field at a point given like for closest_cell, interpolated
+/// linearly between the three cell centres around it
+///
+/// The centres of the lattice form equilateral triangles, between two rows
+/// they alternate pointing up and down. Cells outside of the shape are left
+/// out, without any cell around the point the closest one is used.
+pub fn interpolate(field: &HexField, hex_grid: &CellMask, x: f64, y: f64) -> f64 {
+    let row: f64 = (y/SimulationParams::SI60).floor();
+    let t: f64 = y/SimulationParams::SI60 - row;
+    let odd: i64 = (row as i64).rem_euclid(2);
+    let a: f64 = (x - SimulationParams::CO60*odd as f64).floor();
+    let u: f64 = x - SimulationParams::CO60*odd as f64 - a;
+    let (r, a): (i64, i64) = (row as i64, a as i64);
+    // Corners of the triangle as column, row and weight, the centre above a in the next row has column a + odd
+    let corners: [(i64, i64, f64); 3] = if t <= 2.0*u && t <= 2.0*(1.0 - u) {
+        [(a, r, 1.0 - u - 0.5*t), (a + 1, r, u - 0.5*t), (a + odd, r + 1, t)]
+    } else if t > 2.0*u {
+        [(a, r, 1.0 - t), (a + odd - 1, r + 1, 0.5*t - u), (a + odd, r + 1, u + 0.5*t)]
+    } else {
+        [(a + 1, r, 1.0 - t), (a + odd, r + 1, 1.0 + 0.5*t - u), (a + odd + 1, r + 1, u - 1.0 + 0.5*t)]
+    };
+    let mut sum: f64 = 0.0;
+    let mut weights: f64 = 0.0;
+    for &(i_x, i_y, weight) in corners.iter() {
+        if i_x >= 0 && i_y >= 0 && (i_x as usize) < field.x_len() && (i_y as usize) < field.y_len()
+                && hex_grid[i_y as usize][i_x as usize] != CellKind::Outside {
+            sum += weight*field[i_y as usize][i_x as usize];
+            weights += weight;
+        }
+    }
+    if weights > 0.0 {
+        return sum/weights
+    }
+    let (i_x, i_y) = closest_cell(x, y, field.x_len(), field.y_len());
+    field[i_y][i_x]
+}
+
+/// Colour scale and shading of a picture of a field
+pub struct Shading {
+    /// Magnitude at the ends of the colour map
+    pub max_val: f64,
+    /// Interpolates between the cell centres instead of filling the hexagons
+    pub smooth: bool,
+    /// Colour of the wall cells, left black without one
+    pub wall_colour: Option<[u8; 3]>,
+}
+
+/// Returns the RGB image of a field as it lies on the lattice, width by height
+/// pixels covering the picture of picture_size
+///
+/// Every pixel inside the hexagon of a cell of the shape is coloured, with the
+/// value of that cell or, with smooth shading, the value interpolated between
+/// the cell centres around it. Colours are scaled like field_image, wall
+/// cells get the wall colour if one is given.
+pub fn picture(field: &HexField, hex_grid: &CellMask, cmap: &[[u8; 3]; 256], width: usize, height: usize, shading: &Shading) -> Vec<u8> {
+    let xr: usize = field.x_len();
+    let yr: usize = field.y_len();
+    let (picture_width, picture_height) = picture_size(xr, yr);
+    let mut image_vec: Vec<u8> = vec!(0; width * height * 3);
+    for p_y in 0..height {
+        // Position of the pixel centre in cell spacings, relative to the first cell centre
+        let y: f64 = (p_y as f64 + 0.5)/height as f64*picture_height - 0.5*SimulationParams::SI60;
+        for p_x in 0..width {
+            let x: f64 = (p_x as f64 + 0.5)/width as f64*picture_width - 0.5;
+            let (i_x, i_y) = closest_cell(x, y, xr, yr);
+            let pixel: usize = (p_y*width + p_x)*3;
+            match (hex_grid[i_y][i_x], shading.wall_colour) {
+                (CellKind::Outside, _) => {}
+                (CellKind::Wall, Some(cols)) => image_vec[pixel..pixel + 3].copy_from_slice(&cols),
+                _ => {
+                    let value: f64 = if shading.smooth {interpolate(field, hex_grid, x, y)} else {field[i_y][i_x]};
+                    let cols = determine_color(value, cmap, 0.0, 2.0*shading.max_val);
+                    image_vec[pixel..pixel + 3].copy_from_slice(&cols);
+                }
+            }
+        }
+    }
+    image_vec
+}
+
+/// Parses CSV into a string vector
+pub fn csv_parse(path: &str, delimiter: char) -> Vec<Vec<String>> {
+    // Setting up error message
+    let mut error_message: String = String::from_str("Something went wrong reading the following file: ").expect("Could not parse");
+    error_message.push_str(path);
+    // Loading file with data
+    let content = std::fs::read_to_string(path).expect(&error_message);
+    // Setting up string vectors to fill
+    let mut data_array: Vec<Vec<String>> = Vec::new();
+    let mut string_array: Vec<String> = Vec::new();
+    let mut string: String = "".to_string();
+    // Filling vectors with data
+    for character in content.chars() {
+        if character == delimiter {
+            string_array.push(string.clone());
+            string = "".to_string();
+        } else if character == '\n' {
+            string_array.push(string.clone());
+            string = "".to_string();
+            data_array.push(string_array.clone());
+            string_array = Vec::new();
+        } else {
+            string.push(character)
+        }
+    }
+    data_array
+}
+
+/// Returns the colormap used for visualisation
+pub fn get_cmap(cmap_path: &str) -> [[u8; 3]; 256] {
+    // Loading file with cmap data
+    let data_array: Vec<Vec<String>> = csv_parse(cmap_path, ',');
+    // Setting up u8 array to fill
+    let mut rgb_array: [[u8; 3]; 256] = [[0; 3]; 256];
+    // Filling array with string data
+    for i in 0..data_array.len() {
+        rgb_array[i][0] = (f64::from_str(&data_array[i][1]).unwrap()*255.0).round() as u8;
+        rgb_array[i][1] = (f64::from_str(&data_array[i][2]).unwrap()*255.0).round() as u8;
+        rgb_array[i][2] = (f64::from_str(&data_array[i][3]).unwrap()*255.0).round() as u8;
+    }
+    rgb_array
+}
+
+/// Returns the maximum absolute value of a field
+pub fn get_max_abs(obj: &HexField) -> f64 {
+    let mut current_max: f64 = 0.0;
+    
+    for i1 in 0..obj.y_len() {
+        for value in obj[i1].iter() {
+            if value.abs() > current_max {
+                current_max = value.abs();
+            }
+        }
+    }
+    current_max
+}
+
+/// Returns the value of a Gaussian
+/// 
+/// - height: peak height
+/// - stdv: standard deviation
+/// - x: deviation from the peak
+pub fn gaussian(height: f64, stdv: f64, x: f64) -> f64 {
+    height*(-x.powi(2) / (2.0*stdv.powi(2))).exp()
+}
+
+/// Gives the distance of two points on the grid
+pub fn grid_distance(i_x_1: usize, i_y_1: usize, i_x_2: usize, i_y_2: usize, x_off: usize, y_off: usize, spacing: f64) -> f64 {
+    let (x_1, y_1) = get_cord(i_x_1, i_y_1, x_off, y_off, spacing);
+    let (x_2, y_2) = get_cord(i_x_2, i_y_2, x_off, y_off, spacing);
+    ((x_1 - x_2).powi(2) + (y_1 - y_2).powi(2)).sqrt()
 }
\ No newline at end of file
@@ -0,0 +1,1276 @@
+use std::sync::Arc;
+use std::time::Duration;
+use druid::widget::prelude::*;
+use druid::kurbo::{Rect, Circle, Point, BezPath, Line};
+use druid::widget::{Flex, Label, Switch, Either, Slider, Padding, RadioGroup, List, Scroll, Checkbox, Button, SizedBox, Stepper, Controller};
+use druid::{AppLauncher, WindowDesc, Widget, WidgetExt, RenderContext, Data, Lens, LensExt, Color};
+use druid::{AppDelegate, DelegateCtx, Target, Command, FileDialogOptions, FileSpec, commands, ExtEventSink, Selector, KeyCode, WindowId};
+use crate::boundary::BoundaryCondition;
+use crate::cell::CellMask;
+use crate::checkpoint;
+use crate::export;
+use crate::field::HexField;
+use crate::functions;
+use crate::modes::{self, Modes};
+use crate::renderer::{FieldRenderer, Layer, Overlays, PROBE_COLOURS};
+use crate::params::SimulationParams;
+use crate::probe::ProbeSeries;
+use crate::scenario::Scenario;
+use crate::shape::Shape;
+use crate::simulation::SimulationData;
+use crate::solver::{Solver, Settings, Edit, Frame, FRAME_READY};
+use crate::source::{Source, SourceKind};
+use crate::spectrum::{self, Spectrum, Window};
+
+
+#[derive(Clone, Data, Lens)]
+struct AppData {
+    edit_active: bool,
+    cc_size: f64,
+    anim_data: SimulationData,
+    /// Target number of frames drawn per second
+    frame_rate: f64,
+    /// Time steps computed between two frames
+    steps_per_frame: f64,
+    /// Time steps computed per second, measured while running
+    steps_per_second: f64,
+    anim_paused: bool,
+    /// Thread stepping the simulation, see SolverLink
+    solver: Solver,
+    /// Number of simulations and edits sent to the solver thread, see Frame::changes
+    solver_changes: u32,
+    /// Number of time steps of the "Step N" button
+    step_count: f64,
+    /// Pauses the run when it reaches the step limit of the simulation
+    stop_at_limit: bool,
+    anim_height: f64,
+    radio_status: LiveCursorRadio,
+    initial_strength: f64,
+    paint_speed: f64,
+    paint_damping: f64,
+    source_kind: SourceKind,
+    source_frequency: f64,
+    params_edit: SimulationParams,
+    cmap_path: String,
+    file_action: FileAction,
+    /// Pixels across a hexagon of a saved frame, 1 saves one pixel per cell
+    frame_scale: f64,
+    /// Set while the frames are recorded to record_path
+    recording: bool,
+    record_path: String,
+    /// Records and exports only every nth time step
+    record_every: f64,
+    /// Set while the fields are exported to export_path
+    exporting: bool,
+    export_path: String,
+    /// Index of the probe shown in the spectrum view
+    spectrum_probe: f64,
+    spectrum_window: Window,
+    /// Spectrum shown in the spectrum view, see ProbeSpectrum
+    spectrum: Option<Arc<ProbeSpectrum>>,
+    /// Eigenmodes of the grid, computed in the background on request
+    modes: Arc<Modes>,
+    mode_count: f64,
+    modes_computing: bool,
+    /// Index of the mode shown and started from
+    mode_index: f64,
+    show_mode: bool,
+    /// Shows the energy plot under the simulation
+    show_energy: bool,
+    /// Interpolates the field between the cell centres instead of filling the hexagons
+    smooth_shading: bool,
+    /// Colours the wall cells around the shape
+    show_walls: bool,
+}
+
+impl AppData {
+    /// Replaces the simulation with the one of a scenario file
+    fn load_scenario(&mut self, path: &str) {
+        let scenario: Scenario = match Scenario::load(path) {
+            Ok(scenario) => scenario,
+            Err(error) => {
+                println!("{}", error);
+                return;
+            }
+        };
+        let cmap_path: String = scenario.colormap.clone().unwrap_or(self.cmap_path.clone());
+        let sim: SimulationData = match scenario.build(Arc::new(functions::get_cmap(&cmap_path))) {
+            Ok(sim) => sim,
+            Err(error) => {
+                println!("Could not build scenario {}: {}", path, error);
+                return;
+            }
+        };
+        self.load(sim);
+        self.cmap_path = cmap_path;
+        self.frame_rate = 1000.0/scenario.frame_interval.max(1) as f64;
+        self.steps_per_frame = scenario.steps_per_frame as f64;
+        self.params_edit = self.anim_data.params;
+    }
+
+    /// Replaces the simulation of the window and of the solver thread
+    fn load(&mut self, sim: SimulationData) {
+        self.anim_data = sim;
+        self.solver_changes += 1;
+        self.solver.load(self.anim_data.clone());
+    }
+
+    /// Changes the simulation of the window and of the solver thread, see Edit
+    fn edit(&mut self, edit: Edit) {
+        edit.apply(&mut self.anim_data);
+        self.solver_changes += 1;
+        self.solver.edit(edit);
+    }
+
+    /// Saves the setup of the run with the pulses, sources and painted maps, see Scenario::capture
+    fn save_scenario(&self, path: &str) -> Result<(), String> {
+        Scenario::capture(&self.anim_data, Some(self.cmap_path.clone()), self.frame_interval().as_millis() as u64,
+            self.steps_per_frame as u32).save(path)
+    }
+
+    /// Returns the time between two frames at the target frame rate
+    fn frame_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0/self.frame_rate)
+    }
+
+    /// Starts running, shows the simulation if the initial state was edited
+    fn play(&mut self) {
+        self.edit_active = false;
+        self.anim_paused = self.anim_data.diverged;
+    }
+
+    fn pause(&mut self) {
+        self.anim_paused = true;
+        self.steps_per_second = 0.0;
+        self.solver.cancel_steps();
+    }
+
+    fn toggle_play(&mut self) {
+        if self.anim_paused {
+            self.play();
+        } else {
+            self.pause();
+        }
+    }
+
+    /// Runs the given number of time steps and pauses
+    fn step(&mut self, count: u32) {
+        if !self.anim_data.diverged {
+            self.edit_active = false;
+            self.anim_paused = true;
+            self.solver.step(count);
+        }
+    }
+
+    /// Returns to the state the run started from, see SimulationData::reset
+    fn reset(&mut self) {
+        self.pause();
+        self.edit(Edit::Reset);
+    }
+
+    fn clear_field(&mut self) {
+        self.pause();
+        self.edit(Edit::ClearField);
+    }
+
+    /// Returns how the solver thread is to step the simulation
+    fn settings(&self) -> Settings {
+        Settings {
+            running: !self.anim_paused && !self.edit_active,
+            steps_per_frame: (self.steps_per_frame as u32).max(1),
+            frame_interval: self.frame_interval(),
+            limit: if self.stop_at_limit {Some(self.anim_data.n_max)} else {None},
+            record: if self.recording {Some((self.record_path.clone(), self.record_every as u32, self.frame_scale as usize, self.smooth_shading))} else {None},
+            export: if self.exporting {Some((self.export_path.clone(), self.record_every as u32))} else {None},
+            energy_every: if self.show_energy {1} else {0},
+        }
+    }
+
+    /// Returns the selected eigenmode with its frequency if the modes belong to
+    /// the current grid
+    fn selected_mode(&self) -> Option<(&HexField, f64)> {
+        let k: usize = self.mode_index as usize;
+        let shape: &HexField = self.modes.shapes.get(k)?;
+        let grid: &CellMask = &self.anim_data.hex_grid;
+        if shape.x_len() != grid.x_len() || shape.y_len() != grid.y_len() {
+            return None
+        }
+        Some((shape, self.modes.frequencies[k]))
+    }
+
+    /// Replaces the simulation with the state saved in a checkpoint
+    fn load_checkpoint(&mut self, path: &str) {
+        match checkpoint::load(path, self.anim_data.cmap.clone()) {
+            Ok(sim) => {
+                self.load(sim);
+                self.params_edit = self.anim_data.params;
+            }
+            Err(error) => println!("{}", error),
+        }
+    }
+}
+
+/// What the file chosen in a file dialog is used for
+#[derive(Clone, Copy, PartialEq, Data)]
+enum FileAction {
+    LoadScenario,
+    SaveScenario,
+    LoadCheckpoint,
+    SaveCheckpoint,
+    SaveFrame,
+    Record,
+    ExportField,
+    ExportSeries,
+    ExportProbes,
+    ExportEnergy,
+}
+
+const SCENARIO_FILE: FileSpec = FileSpec::new("Scenario", &["toml"]);
+const CHECKPOINT_FILE: FileSpec = FileSpec::new("Checkpoint", &["wave"]);
+const PNG_FILE: FileSpec = FileSpec::new("PNG image", &["png"]);
+const GIF_FILE: FileSpec = FileSpec::new("GIF animation", &["gif"]);
+const CSV_FILE: FileSpec = FileSpec::new("CSV table", &["csv"]);
+const NPY_FILE: FileSpec = FileSpec::new("NumPy array", &["npy"]);
+
+/// Starts the computation of the eigenmodes of the current grid
+const COMPUTE_MODES: Selector = Selector::new("wave.compute-modes");
+/// Delivers the computed eigenmodes from the background thread
+const MODES_READY: Selector<Arc<Modes>> = Selector::new("wave.modes-ready");
+/// Computes the spectrum of the spectrum view again after its probe, window or recording changed
+const REFRESH_SPECTRUM: Selector = Selector::new("wave.refresh-spectrum");
+
+/// Handles the files chosen in the file dialogs and the computation of the
+/// eigenmodes, which runs on its own thread and reports back through the sink
+struct Delegate {
+    sink: ExtEventSink,
+}
+
+impl AppDelegate<AppData> for Delegate {
+    /// Handles the keyboard shortcuts of the transport controls, whichever widget has the focus
+    fn event(&mut self, _ctx: &mut DelegateCtx, _window_id: WindowId, event: Event, data: &mut AppData, _env: &Env) -> Option<Event> {
+        if let Event::KeyDown(key_event) = &event {
+            if key_event.mods.ctrl || key_event.mods.alt || key_event.mods.meta {
+                return Some(event)
+            }
+            match key_event.key_code {
+                KeyCode::Space if !key_event.is_repeat => data.toggle_play(),
+                KeyCode::Period | KeyCode::ArrowRight => data.step(1),
+                KeyCode::KeyN if !key_event.is_repeat => data.step(data.step_count as u32),
+                KeyCode::KeyR if !key_event.is_repeat => data.reset(),
+                KeyCode::KeyC if !key_event.is_repeat => data.clear_field(),
+                _ => return Some(event),
+            }
+            return None
+        }
+        Some(event)
+    }
+
+    fn command(&mut self, _ctx: &mut DelegateCtx, _target: Target, cmd: &Command, data: &mut AppData, _env: &Env) -> bool {
+        if cmd.is(COMPUTE_MODES) {
+            if !data.modes_computing {
+                data.modes_computing = true;
+                let sink: ExtEventSink = self.sink.clone();
+                let hex_grid: Arc<CellMask> = data.anim_data.hex_grid.clone();
+                let hex_speed: Arc<HexField> = data.anim_data.hex_speed.clone();
+                let params: SimulationParams = data.anim_data.params;
+                let count: usize = data.mode_count as usize;
+                std::thread::spawn(move || {
+                    let modes: Modes = modes::solve(&hex_grid, &hex_speed, &params, count);
+                    // Fails only once the window is closed, then nobody waits for the modes
+                    let _ = sink.submit_command(MODES_READY, Arc::new(modes), None);
+                });
+            }
+            return false
+        }
+        if let Some(modes) = cmd.get(MODES_READY) {
+            data.modes = modes.clone();
+            data.modes_computing = false;
+            data.mode_index = data.mode_index.min((modes.shapes.len() as f64 - 1.0).max(0.0));
+            return false
+        }
+        if let Some(file_info) = cmd.get(commands::OPEN_FILE) {
+            let path: String = file_info.path().to_string_lossy().into_owned();
+            match data.file_action {
+                FileAction::LoadScenario => data.load_scenario(&path),
+                FileAction::LoadCheckpoint => data.load_checkpoint(&path),
+                _ => {}
+            }
+            return false
+        }
+        if let Some(Some(file_info)) = cmd.get(commands::SAVE_FILE) {
+            let path: String = file_info.path().to_string_lossy().into_owned();
+            let result: Result<(), String> = match data.file_action {
+                FileAction::SaveScenario => data.save_scenario(&path),
+                FileAction::SaveCheckpoint => checkpoint::save(&path, &data.anim_data),
+                FileAction::SaveFrame => export::write_png(&path, &data.anim_data, data.frame_scale as usize, data.smooth_shading),
+                FileAction::Record => {
+                    data.record_path = path;
+                    data.recording = true;
+                    Ok(())
+                }
+                FileAction::ExportField => export::write_field(&path, &data.anim_data),
+                FileAction::ExportProbes => export::write_probes(&path, &data.anim_data),
+                FileAction::ExportEnergy if data.anim_data.energy.samples.is_empty() =>
+                    Err(String::from("No energy recorded, it is measured while \"Plot\" under \"Energy\" is on")),
+                FileAction::ExportEnergy => export::write_energy(&path, &data.anim_data),
+                FileAction::ExportSeries => {
+                    data.export_path = path;
+                    data.exporting = true;
+                    Ok(())
+                }
+                _ => Ok(()),
+            };
+            if let Err(error) = result {
+                println!("{}", error);
+            }
+            return false
+        }
+        true
+    }
+}
+
+/// Keeps the solver thread in step with the window
+///
+/// Frames of the thread replace the simulation of the window unless it was
+/// changed since, changes of the simulation reach the thread through
+/// AppData::load and AppData::edit. Wraps the whole window so it sees every
+/// change of the settings, the simulation widget is hidden while the initial
+/// state is edited.
+struct SolverLink;
+
+impl<W: Widget<AppData>> Controller<AppData, W> for SolverLink {
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut AppData, env: &Env) {
+        if let Event::Command(cmd) = event {
+            if let Some(frame) = cmd.get(FRAME_READY) {
+                self.apply(frame, data);
+                refresh_spectrum(data);
+                return;
+            }
+            if cmd.is(REFRESH_SPECTRUM) {
+                refresh_spectrum(data);
+                return;
+            }
+        }
+        child.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(&mut self, child: &mut W, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &AppData, env: &Env) {
+        if let LifeCycle::WidgetAdded = event {
+            data.solver.configure(data.settings());
+        }
+        child.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(&mut self, child: &mut W, ctx: &mut UpdateCtx, old_data: &AppData, data: &AppData, env: &Env) {
+        if data.settings() != old_data.settings() {
+            data.solver.configure(data.settings());
+        }
+        // The spectrum can only be replaced while handling an event
+        if !spectrum_current(data) {
+            ctx.submit_command(REFRESH_SPECTRUM, ctx.widget_id());
+        }
+        child.update(ctx, old_data, data, env);
+    }
+}
+
+/// Sends the change a widget made to the simulation of the window on to the
+/// solver thread, as the edit the function returns for the changed simulation
+struct EditLink(fn(&SimulationData) -> Edit);
+
+impl<W: Widget<AppData>> Controller<AppData, W> for EditLink {
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut AppData, env: &Env) {
+        let before: SimulationData = data.anim_data.clone();
+        child.event(ctx, event, data, env);
+        if !before.same(&data.anim_data) {
+            data.solver_changes += 1;
+            data.solver.edit((self.0)(&data.anim_data));
+        }
+    }
+}
+
+impl SolverLink {
+    fn apply(&mut self, frame: &Frame, data: &mut AppData) {
+        // The window has applied changes the frame does not hold yet, a later one will
+        if frame.changes == data.solver_changes {
+            data.anim_data = frame.sim.clone();
+        }
+        if frame.paused {
+            data.anim_paused = true;
+        }
+        if frame.recording_closed {
+            data.recording = false;
+        }
+        if frame.export_closed {
+            data.exporting = false;
+        }
+        data.steps_per_second = frame.steps_per_second;
+        for error in frame.errors.iter() {
+            println!("{}", error);
+        }
+    }
+}
+
+struct SimulationWidget {
+    cell_ratio: f64,
+    renderer: FieldRenderer,
+}
+
+impl Widget<AppData> for SimulationWidget {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut AppData, _env: &Env) {
+        if let Event::WindowConnected = event {
+            ctx.request_paint();
+        }
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &AppData, _env: &Env) {
+        if let LifeCycle::WidgetAdded = event {
+            self.cell_ratio = picture_ratio(&data.anim_data.hex_grid);
+            ctx.request_layout();
+            ctx.request_paint();
+        }
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppData, data: &AppData, _env: &Env) {
+        // The grid changes its size when it is rebuilt with a new spacing
+        if !old_data.anim_data.hex_grid.same(&data.anim_data.hex_grid) {
+            self.cell_ratio = picture_ratio(&data.anim_data.hex_grid);
+            ctx.request_layout();
+            ctx.request_paint();
+        }
+        if old_data.anim_height != data.anim_height {
+            ctx.request_layout();
+            ctx.request_paint();
+        }
+        if old_data.smooth_shading != data.smooth_shading || old_data.show_walls != data.show_walls {
+            ctx.request_paint();
+        }
+        // Every frame of the solver thread comes with its own copy of the field
+        if old_data.anim_data.revision != data.anim_data.revision || !old_data.anim_data.levels.same(&data.anim_data.levels) {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(&mut self, _layout_ctx: &mut LayoutCtx, _bc: &BoxConstraints, data: &AppData, _env: &Env) -> Size {
+        Size::new(data.anim_height*self.cell_ratio, data.anim_height)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppData, _env: &Env) {
+        // A diverged field holds no information worth drawing
+        if data.anim_data.diverged {
+            ctx.fill(Rect{x0: 0.0, y0: 0.0, x1: data.anim_height*self.cell_ratio, y1: data.anim_height}, &Color::rgb8(60, 20, 20));
+            return;
+        }
+        let overlays: Overlays = Overlays{walls: data.show_walls, sources: false, probes: true, cursor: None};
+        self.renderer.paint(ctx, &data.anim_data, Layer::wave(&data.anim_data), ctx.size(), data.smooth_shading, &overlays);
+    }
+}
+
+/// Number of the most recent time steps shown in the probe plot
+const PLOT_SAMPLES: usize = 1000;
+
+/// Returns the width over the height of the picture of a grid, see functions::picture_size
+fn picture_ratio(hex_grid: &CellMask) -> f64 {
+    let (width, height) = functions::picture_size(hex_grid.x_len(), hex_grid.y_len());
+    width/height
+}
+
+/// Scrolling line plot of the values recorded by the probes
+struct ProbePlot;
+
+impl Widget<AppData> for ProbePlot {
+    fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut AppData, _env: &Env) {}
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &AppData, _env: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppData, data: &AppData, _env: &Env) {
+        if old_data.anim_data.n != data.anim_data.n || !old_data.anim_data.probes.same(&data.anim_data.probes)
+            || !old_data.anim_data.probe_series.same(&data.anim_data.probe_series) {
+            ctx.request_paint();
+        }
+        if old_data.anim_height != data.anim_height {
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(&mut self, _layout_ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &AppData, _env: &Env) -> Size {
+        // As wide as the view of the grid
+        let width: f64 = data.anim_height*picture_ratio(&data.anim_data.hex_grid);
+        bc.constrain(Size::new(width, 150.0))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppData, _env: &Env) {
+        let size: Size = ctx.size();
+        ctx.fill(size.to_rect(), &Color::rgb8(20, 20, 20));
+        let middle: f64 = size.height/2.0;
+        ctx.stroke(Line::new((0.0, middle), (size.width, middle)), &Color::rgb8(80, 80, 80), 1.0);
+        // Scaling to the largest magnitude shown
+        let series = &data.anim_data.probe_series;
+        let start: usize = series.times.len().saturating_sub(PLOT_SAMPLES);
+        let max_abs: f64 = series.values.iter().flat_map(|values| values.range(start..)).fold(0.0, |max: f64, value| max.max(value.abs()));
+        if max_abs == 0.0 || !max_abs.is_finite() {
+            return;
+        }
+        for (k, values) in series.values.iter().enumerate() {
+            let mut path = BezPath::new();
+            for (i, value) in values.range(start..).enumerate() {
+                let point = Point{
+                    x: i as f64 / (PLOT_SAMPLES - 1) as f64 * size.width,
+                    y: middle - value/max_abs*(middle - 5.0)};
+                if i == 0 {
+                    path.move_to(point);
+                } else {
+                    path.line_to(point);
+                }
+            }
+            ctx.stroke(path, &PROBE_COLOURS[k%PROBE_COLOURS.len()], 1.0);
+        }
+    }
+}
+
+/// Colours of the total, kinetic and potential energy in the energy plot
+const ENERGY_COLOURS: [Color; 3] = [Color::rgb8(255, 255, 255), Color::rgb8(240, 80, 80), Color::rgb8(70, 210, 230)];
+
+/// Plot of the total, kinetic and potential energy over the whole run, a drift
+/// of the total shows energy gained or lost at the boundary or a coming blow up
+struct EnergyPlot;
+
+impl Widget<AppData> for EnergyPlot {
+    fn event(&mut self, _ctx: &mut EventCtx, _event: &Event, _data: &mut AppData, _env: &Env) {}
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &AppData, _env: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppData, data: &AppData, _env: &Env) {
+        if old_data.anim_data.n != data.anim_data.n || !old_data.anim_data.energy.same(&data.anim_data.energy) {
+            ctx.request_paint();
+        }
+        if old_data.anim_height != data.anim_height {
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(&mut self, _layout_ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &AppData, _env: &Env) -> Size {
+        let width: f64 = data.anim_height*picture_ratio(&data.anim_data.hex_grid);
+        bc.constrain(Size::new(width, 150.0))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppData, _env: &Env) {
+        let size: Size = ctx.size();
+        ctx.fill(size.to_rect(), &Color::rgb8(20, 20, 20));
+        let series = &data.anim_data.energy;
+        let samples = &series.samples;
+        if samples.len() < 2 {
+            return;
+        }
+        // Scaling to the range of all energies, including zero
+        let (min, max): (f64, f64) = (series.min, series.max);
+        if max - min == 0.0 || !(max - min).is_finite() {
+            return;
+        }
+        let zero: f64 = size.height - 5.0 + min/(max - min)*(size.height - 10.0);
+        ctx.stroke(Line::new((0.0, zero), (size.width, zero)), &Color::rgb8(80, 80, 80), 1.0);
+        // One point per pixel column, picked from the whole run
+        let columns: usize = (size.width as usize).max(2);
+        for (k, colour) in ENERGY_COLOURS.iter().enumerate() {
+            let mut path = BezPath::new();
+            for column in 0..columns {
+                let sample = &samples[column*(samples.len() - 1)/(columns - 1)];
+                let value: f64 = [sample.total(), sample.kinetic, sample.potential][k];
+                let point = Point{
+                    x: column as f64 / (columns - 1) as f64 * size.width,
+                    y: size.height - 5.0 - (value - min)/(max - min)*(size.height - 10.0)};
+                if column == 0 {
+                    path.move_to(point);
+                } else {
+                    path.line_to(point);
+                }
+            }
+            ctx.stroke(path, colour, 1.0);
+        }
+    }
+}
+
+/// Lists the energy of the last time step with its drift and the norms of the field
+fn energy_label(data: &AppData) -> String {
+    let series = &data.anim_data.energy;
+    match series.samples.last() {
+        Some(sample) => {
+            let drift: String = series.drift().map(|drift| format!("{:+.3e}", drift)).unwrap_or(String::from("-"));
+            format!("Energy {:.4e} (kinetic {:.3e}, potential {:.3e}), drift {}, L2 {:.3e}, max {:.3e}",
+                sample.total(), sample.kinetic, sample.potential, drift, sample.l2_norm, sample.max_norm)
+        }
+        None => String::from("Energy -"),
+    }
+}
+
+/// Number of the most recent samples of a probe analysed by the spectrum view
+const SPECTRUM_SAMPLES: usize = 16384;
+/// Number of peaks marked in the spectrum view
+const SPECTRUM_PEAKS: usize = 8;
+
+/// Returns the index of the probe shown in the spectrum view if there are probes
+fn spectrum_probe(data: &AppData) -> Option<usize> {
+    let count: usize = data.anim_data.probes.len();
+    if count == 0 {
+        return None
+    }
+    Some((data.spectrum_probe as usize).min(count - 1))
+}
+
+/// Spectrum of the probe shown in the spectrum view with its strongest peaks
+///
+/// Computed once for every recording, probe and window rather than in every
+/// paint of the plot and every update of its label. The recording is
+/// replaced by every time step, so a run computes it once per frame.
+struct ProbeSpectrum {
+    series: Arc<ProbeSeries>,
+    probe: usize,
+    window: Window,
+    spectrum: Spectrum,
+    peaks: Vec<(f64, f64)>,
+}
+
+/// Returns true if the spectrum of the spectrum view belongs to the current recording, probe and window
+fn spectrum_current(data: &AppData) -> bool {
+    match (&data.spectrum, spectrum_probe(data)) {
+        (Some(shown), Some(k)) => Arc::ptr_eq(&shown.series, &data.anim_data.probe_series) && shown.probe == k
+            && shown.window == data.spectrum_window,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Computes the spectrum of the spectrum view if it is not current
+fn refresh_spectrum(data: &mut AppData) {
+    if spectrum_current(data) {
+        return;
+    }
+    data.spectrum = spectrum_probe(data).map(|k| {
+        let spectrum: Spectrum = data.anim_data.probe_series.spectrum(k, data.spectrum_window, SPECTRUM_SAMPLES);
+        let peaks: Vec<(f64, f64)> = spectrum::find_peaks(&spectrum, SPECTRUM_PEAKS);
+        Arc::new(ProbeSpectrum {
+            series: data.anim_data.probe_series.clone(),
+            probe: k,
+            window: data.spectrum_window,
+            spectrum,
+            peaks,
+        })
+    });
+}
+
+/// Amplitude spectrum of the selected probe
+///
+/// Clicking a peak adds a sine source at the cell of the probe oscillating at
+/// the frequency of the peak, which excites the resonance behind it.
+struct SpectrumPlot {
+    /// Horizontal position and frequency of the peaks of the last paint
+    peaks: Vec<(f64, f64)>,
+}
+
+impl Widget<AppData> for SpectrumPlot {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut AppData, _env: &Env) {
+        if let Event::MouseDown(mouse_event) = event {
+            let closest = self.peaks.iter().min_by(|a, b| (a.0 - mouse_event.pos.x).abs().total_cmp(&(b.0 - mouse_event.pos.x).abs()));
+            if let (Some(&(x, frequency)), Some(k)) = (closest, spectrum_probe(data)) {
+                if (x - mouse_event.pos.x).abs() < 8.0 {
+                    let probe = data.anim_data.probes[k];
+                    data.source_frequency = frequency;
+                    match Source::new(SourceKind::Sine, probe.i_x, probe.i_y, data.initial_strength, frequency) {
+                        Ok(source) => data.edit(Edit::PlaceSource(source)),
+                        Err(error) => println!("Could not add source: {}", error),
+                    }
+                    ctx.request_paint();
+                }
+            }
+        }
+    }
+
+    fn lifecycle(&mut self, _ctx: &mut LifeCycleCtx, _event: &LifeCycle, _data: &AppData, _env: &Env) {}
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppData, data: &AppData, _env: &Env) {
+        if !old_data.spectrum.same(&data.spectrum) {
+            ctx.request_paint();
+        }
+        if old_data.anim_height != data.anim_height {
+            ctx.request_layout();
+        }
+    }
+
+    fn layout(&mut self, _layout_ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &AppData, _env: &Env) -> Size {
+        let width: f64 = data.anim_height*picture_ratio(&data.anim_data.hex_grid);
+        bc.constrain(Size::new(width, 150.0))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppData, _env: &Env) {
+        let size: Size = ctx.size();
+        ctx.fill(size.to_rect(), &Color::rgb8(20, 20, 20));
+        self.peaks.clear();
+        let (k, spectrum): (usize, &Spectrum) = match &data.spectrum {
+            Some(shown) => (shown.probe, &shown.spectrum),
+            None => return,
+        };
+        let max: f64 = spectrum.amplitudes.iter().cloned().fold(0.0, f64::max);
+        if max == 0.0 || !max.is_finite() {
+            return;
+        }
+        // Showing the frequencies up to the last bin holding a noticeable amplitude
+        let last: usize = spectrum.amplitudes.iter().rposition(|&amplitude| amplitude > 1e-3*max).unwrap_or(0);
+        let bins: usize = (last + last/10 + 2).min(spectrum.amplitudes.len());
+        let f_max: f64 = spectrum.frequencies[bins - 1];
+        let to_point = |frequency: f64, amplitude: f64| Point{
+            x: frequency/f_max*size.width,
+            y: size.height - 5.0 - amplitude/max*(size.height - 10.0)};
+        let mut path = BezPath::new();
+        path.move_to(to_point(spectrum.frequencies[0], spectrum.amplitudes[0]));
+        for i in 1..bins {
+            path.line_to(to_point(spectrum.frequencies[i], spectrum.amplitudes[i]));
+        }
+        ctx.stroke(path, &PROBE_COLOURS[k%PROBE_COLOURS.len()], 1.0);
+        // Marking the peaks that can be clicked
+        for &(frequency, amplitude) in data.spectrum.iter().flat_map(|shown| shown.peaks.iter()) {
+            let point = to_point(frequency, amplitude);
+            ctx.stroke(Circle{center: point, radius: 4.0}, &Color::rgb8(230, 230, 230), 1.5);
+            self.peaks.push((point.x, frequency));
+        }
+    }
+}
+
+/// Lists the strongest peaks of the spectrum view
+fn spectrum_label(data: &AppData) -> String {
+    match &data.spectrum {
+        Some(shown) => {
+            let peaks: Vec<String> = shown.peaks.iter().take(4).map(|(frequency, _amplitude)| format!("{:.3}", frequency)).collect();
+            format!("Probe {} peaks: {}", shown.probe + 1, peaks.join(", "))
+        }
+        None => String::from("No probes"),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Data)]
+enum LiveCursorRadio {
+    Point,
+    Gauss,
+    Speed,
+    Damping,
+    Source,
+    Probe,
+}
+
+struct LiveCursor {
+    punkt: Point,
+    cell_ratio: f64,
+    renderer: FieldRenderer,
+}
+
+impl Widget<AppData> for LiveCursor {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut AppData, _env: &Env) {
+        match event {
+            Event::MouseMove(yekis) => {
+                self.punkt = yekis.pos;
+                if yekis.buttons.has_left() {
+                    let cursor_x_percent_pos: f64 = yekis.pos.x / (data.anim_height*self.cell_ratio);
+                    let cursor_y_percent_pos: f64 = yekis.pos.y / data.anim_height;
+                    match data.radio_status {
+                        LiveCursorRadio::Speed => {data.edit(Edit::PaintSpeed(cursor_x_percent_pos, cursor_y_percent_pos, data.cc_size, data.paint_speed));}
+                        LiveCursorRadio::Damping => {data.edit(Edit::PaintDamping(cursor_x_percent_pos, cursor_y_percent_pos, data.cc_size, data.paint_damping));}
+                        _ => {}
+                    }
+                }
+                ctx.request_anim_frame();
+            }
+            Event::MouseDown(mouse_event) => {
+                let cursor_x_percent_pos: f64 = mouse_event.pos.x / (data.anim_height*self.cell_ratio);
+                let cursor_y_percent_pos: f64 = mouse_event.pos.y / data.anim_height;
+                
+                match data.radio_status {
+                    LiveCursorRadio::Point => {data.edit(Edit::Point(cursor_x_percent_pos, cursor_y_percent_pos, data.initial_strength));}
+                    LiveCursorRadio::Gauss => {data.edit(Edit::Gauss(cursor_x_percent_pos, cursor_y_percent_pos, data.cc_size, data.initial_strength));}
+                    LiveCursorRadio::Speed => {data.edit(Edit::PaintSpeed(cursor_x_percent_pos, cursor_y_percent_pos, data.cc_size, data.paint_speed));}
+                    LiveCursorRadio::Damping => {data.edit(Edit::PaintDamping(cursor_x_percent_pos, cursor_y_percent_pos, data.cc_size, data.paint_damping));}
+                    LiveCursorRadio::Source => {data.edit(Edit::AddSource(cursor_x_percent_pos, cursor_y_percent_pos, data.source_kind, data.initial_strength, data.source_frequency));}
+                    LiveCursorRadio::Probe => {data.edit(Edit::AddProbe(cursor_x_percent_pos, cursor_y_percent_pos));}
+                }
+                ctx.request_paint();
+            }
+            _ => {}
+        }
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &AppData, _env: &Env) {
+        if let LifeCycle::WidgetAdded = event {
+            self.cell_ratio = picture_ratio(&data.anim_data.hex_grid);
+            ctx.request_layout();
+            ctx.request_paint();
+        }
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppData, data: &AppData, _env: &Env) {
+        // The grid changes its size when it is rebuilt with a new spacing
+        if !old_data.anim_data.hex_grid.same(&data.anim_data.hex_grid) {
+            self.cell_ratio = picture_ratio(&data.anim_data.hex_grid);
+            ctx.request_layout();
+            ctx.request_paint();
+        }
+        if old_data.anim_height != data.anim_height {
+            ctx.request_layout();
+            ctx.request_paint();
+        }
+        if old_data.smooth_shading != data.smooth_shading || old_data.show_walls != data.show_walls {
+            ctx.request_paint();
+        }
+        if !old_data.modes.same(&data.modes) || old_data.mode_index != data.mode_index || old_data.show_mode != data.show_mode {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(&mut self, _layout_ctx: &mut LayoutCtx, _bc: &BoxConstraints, data: &AppData, _env: &Env) -> Size {
+        Size::new(data.anim_height*self.cell_ratio, data.anim_height)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &AppData, _env: &Env) {
+        // Showing the speed and damping maps while painting them, and the selected mode if asked to
+        let layer: Layer = match (data.radio_status, data.selected_mode()) {
+            (LiveCursorRadio::Speed, _) => Layer::Map(data.anim_data.hex_speed.clone()),
+            (LiveCursorRadio::Damping, _) => Layer::Map(data.anim_data.hex_damp.clone()),
+            (_, Some(_)) if data.show_mode => Layer::Mode(data.modes.clone(), data.mode_index as usize),
+            _ => Layer::wave(&data.anim_data),
+        };
+        let size: Size = ctx.size();
+        let cursor: Option<(Point, f64)> = if data.edit_active && ctx.is_hot() {
+            let (picture_width, _picture_height) = functions::picture_size(data.anim_data.hex_grid.x_len(), data.anim_data.hex_grid.y_len());
+            Some((self.punkt, (data.cc_size/data.anim_data.params.spacing) / picture_width * size.width))
+        } else {
+            None
+        };
+        let overlays: Overlays = Overlays{walls: data.show_walls, sources: true, probes: true, cursor};
+        self.renderer.paint(ctx, &data.anim_data, layer, size, data.smooth_shading, &overlays);
+    }
+}
+
+
+/// Builds the entry of a source in the list of the side panel
+fn build_source_row() -> impl Widget<Source> {
+    Flex::column()
+        .with_child(Flex::row()
+            .with_child(Checkbox::new("").lens(Source::active))
+            .with_child(Label::new(|data: &Source, _env: &Env| format!("{} {} {}", data.kind.name(), data.i_x, data.i_y)).with_text_size(12.0)))
+        .with_child(Slider::new().with_range(-10.0, 10.0).lens(Source::amplitude))
+        .with_child(Slider::new().with_range(0.1, 10.0).lens(Source::frequency))
+        .with_child(Slider::new().with_range(0.0, 2.0*std::f64::consts::PI).lens(Source::phase))
+        .with_spacer(10.0)
+}
+
+fn build_ui() -> impl Widget<AppData> {
+    let add_initial_options: [(&str, LiveCursorRadio); 6] =
+    [("Point", LiveCursorRadio::Point), ("Gaussian", LiveCursorRadio::Gauss), ("Speed", LiveCursorRadio::Speed),
+    ("Damping", LiveCursorRadio::Damping), ("Source", LiveCursorRadio::Source), ("Probe", LiveCursorRadio::Probe)];
+    let source_options: [(&str, SourceKind); 4] =
+    [("Sine", SourceKind::Sine), ("Ricker", SourceKind::Ricker), ("Chirp", SourceKind::Chirp), ("Pulses", SourceKind::PulseTrain)];
+
+    let button_bar_edit = Flex::column()
+        .with_child(Label::new("Type").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(RadioGroup::new(add_initial_options.to_vec()).lens(AppData::radio_status))
+        .with_spacer(30.0)
+        .with_child(Label::new("Size").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Slider::new().with_range(0.01, 1.0).lens(AppData::cc_size))
+        .with_spacer(30.0)
+        .with_child(Label::new("Strength").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Slider::new().with_range(-10.0, 10.0).lens(AppData::initial_strength))
+        .with_spacer(30.0)
+        .with_child(Label::new("Speed").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Slider::new().with_range(0.05, 2.0).lens(AppData::paint_speed))
+        .with_spacer(30.0)
+        .with_child(Label::new("Damping").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Slider::new().with_range(0.0, 10.0).lens(AppData::paint_damping))
+        .with_spacer(30.0)
+        .with_child(Label::new("Source").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(RadioGroup::new(source_options.to_vec()).lens(AppData::source_kind))
+        .with_spacer(10.0)
+        .with_child(Label::new("Frequency").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Slider::new().with_range(0.1, 10.0).lens(AppData::source_frequency))
+        .with_spacer(30.0)
+        .with_child(Label::new("Sources").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(List::new(build_source_row).lens(AppData::anim_data.then(SimulationData::sources))
+            .controller(EditLink(|sim| Edit::Sources(sim.sources.clone()))))
+        .with_child(Button::new("Clear").on_click(|_ctx, data: &mut AppData, _env| {
+            data.edit(Edit::ClearSources);
+        }))
+        .with_spacer(30.0)
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("Probes {}", data.anim_data.probes.len())).with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Button::new("Clear").on_click(|_ctx, data: &mut AppData, _env| {
+            data.edit(Edit::ClearProbes);
+        }))
+        .with_spacer(30.0)
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("Modes {}", data.mode_count)).with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Stepper::new().with_range(1.0, 32.0).with_step(1.0).lens(AppData::mode_count))
+        .with_spacer(10.0)
+        .with_child(Either::new(|data: &AppData, _env| data.modes_computing,
+            Label::new("Computing...").with_text_size(12.0),
+            Button::new("Compute").on_click(|ctx, _data: &mut AppData, _env| {
+                ctx.submit_command(Command::new(COMPUTE_MODES, ()), None);
+            })))
+        .with_spacer(10.0)
+        .with_child(Label::new(|data: &AppData, _env: &Env| match data.selected_mode() {
+            Some((_shape, frequency)) => format!("Mode {} f {:.4}", data.mode_index, frequency),
+            None => String::from("No modes"),
+        }).with_text_size(12.0))
+        .with_child(Stepper::new().with_range(0.0, 31.0).with_step(1.0).lens(AppData::mode_index))
+        .with_spacer(10.0)
+        .with_child(Checkbox::new("Show").lens(AppData::show_mode))
+        .with_spacer(10.0)
+        .with_child(Button::new("Start").on_click(|_ctx, data: &mut AppData, _env| {
+            // The mode is cloned out of the data before the simulation is changed
+            if let Some((shape, frequency)) = data.selected_mode().map(|(shape, frequency)| (shape.clone(), frequency)) {
+                data.edit(Edit::StartFromMode(shape, frequency, data.initial_strength));
+                data.show_mode = false;
+            }
+        }))
+        .with_spacer(40.0);
+    
+    let window_options: [(&str, Window); 4] =
+    [("Rectangular", Window::Rectangular), ("Hann", Window::Hann), ("Hamming", Window::Hamming), ("Blackman", Window::Blackman)];
+    let boundary_options: [(&str, BoundaryCondition); 3] =
+    [("Reflecting", BoundaryCondition::Reflecting), ("Absorbing", BoundaryCondition::Absorbing), ("Sponge", BoundaryCondition::Sponge)];
+
+    let button_bar_anim = Flex::column()
+        .with_child(Label::new("Boundary").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(RadioGroup::new(boundary_options.to_vec()).lens(AppData::anim_data.then(SimulationData::boundary))
+            .controller(EditLink(|sim| Edit::Boundary(sim.boundary))))
+        .with_spacer(30.0)
+        .with_child(Label::new("Spectrum").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("Probe {}", data.spectrum_probe + 1.0)).with_text_size(12.0))
+        .with_child(Stepper::new().with_range(0.0, 31.0).with_step(1.0).lens(AppData::spectrum_probe))
+        .with_spacer(10.0)
+        .with_child(RadioGroup::new(window_options.to_vec()).lens(AppData::spectrum_window))
+        .with_spacer(30.0)
+        .with_child(Label::new("Energy").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Checkbox::new("Plot").lens(AppData::show_energy))
+        .with_spacer(30.0)
+        .with_child(Label::new("Damping").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Slider::new().with_range(0.0, 10.0).lens(AppData::anim_data.then(SimulationData::damping))
+            .controller(EditLink(|sim| Edit::Damping(sim.damping))))
+        .with_spacer(30.0)
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("Speed {:.3}", data.params_edit.speed)).with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Slider::new().with_range(0.05, 2.0).lens(AppData::params_edit.then(SimulationParams::speed)))
+        .with_spacer(10.0)
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("Spacing {:.4}", data.params_edit.spacing)).with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Slider::new().with_range(0.0025, 0.04).lens(AppData::params_edit.then(SimulationParams::spacing)))
+        .with_spacer(10.0)
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("Time step {:.4}", data.params_edit.time_spacing)).with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Slider::new().with_range(0.001, 0.04).lens(AppData::params_edit.then(SimulationParams::time_spacing)))
+        .with_spacer(10.0)
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("Threads {}", data.params_edit.threads)).with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Stepper::new().with_range(0.0, 64.0).with_step(1.0)
+            .lens(AppData::params_edit.then(SimulationParams::threads).map(|threads| *threads as f64, |threads, value| *threads = value as usize)))
+        .with_spacer(10.0)
+        .with_child(Button::new("Apply").on_click(|_ctx, data: &mut AppData, _env| {
+            // Only a new time step or thread count can be applied without rebuilding the grid
+            if data.params_edit.speed == data.anim_data.params.speed && data.params_edit.spacing == data.anim_data.params.spacing {
+                data.edit(Edit::TimeSpacing(data.params_edit.time_spacing));
+                if data.params_edit.threads != data.anim_data.params.threads {
+                    data.edit(Edit::Threads(data.params_edit.threads));
+                }
+            } else {
+                let rebuilt: SimulationData = data.anim_data.rebuild(data.params_edit);
+                data.load(rebuilt);
+            }
+            data.params_edit = data.anim_data.params;
+        }))
+        .with_spacer(30.0)
+        .with_child(Label::new("Window Size").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Slider::new().with_range(100.0, 1000.0).lens(AppData::anim_height))
+        .with_spacer(10.0)
+        .with_child(Checkbox::new("Smooth").lens(AppData::smooth_shading))
+        .with_spacer(10.0)
+        .with_child(Checkbox::new("Walls").lens(AppData::show_walls))
+        .with_spacer(40.0);
+
+    let flex_button = Either::new(|data, _env| data.edit_active,
+        Scroll::new(button_bar_edit).vertical(),
+        Scroll::new(button_bar_anim).vertical());
+
+    let button_bar = Flex::column()
+        .with_spacer(20.0)
+        .with_child(Label::new("Configure").with_text_size(12.0))
+        .with_child(Label::new("Initial State").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Switch::new().lens(AppData::edit_active))
+        .with_spacer(30.0)
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("Courant {:.2}/{:.2}", data.anim_data.max_courant(), functions::CFL_LIMIT))
+            .with_text_size(12.0))
+        .with_child(Either::new(|data: &AppData, _env| data.anim_data.diverged,
+            Label::new("Diverged, paused").with_text_size(12.0).with_text_color(Color::rgb8(230, 80, 80)),
+            SizedBox::empty()))
+        .with_spacer(30.0)
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("Step {}", data.anim_data.n)).with_text_size(12.0))
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("t = {:.3}", data.anim_data.n as f64*data.anim_data.params.time_spacing))
+            .with_text_size(12.0))
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("{:.0} steps/s", data.steps_per_second)).with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Either::new(|data: &AppData, _env| data.anim_paused,
+            Button::new("Play").on_click(|_ctx, data: &mut AppData, _env| data.play()),
+            Button::new("Pause").on_click(|_ctx, data: &mut AppData, _env| data.pause())))
+        .with_spacer(10.0)
+        .with_child(Button::new("Step").on_click(|_ctx, data: &mut AppData, _env| data.step(1)))
+        .with_spacer(10.0)
+        .with_child(Button::new(|data: &AppData, _env: &Env| format!("Step {}", data.step_count))
+            .on_click(|_ctx, data: &mut AppData, _env| data.step(data.step_count as u32)))
+        .with_child(Stepper::new().with_range(1.0, 1000.0).with_step(10.0).lens(AppData::step_count))
+        .with_spacer(10.0)
+        .with_child(Button::new("Reset").on_click(|_ctx, data: &mut AppData, _env| data.reset()))
+        .with_spacer(10.0)
+        .with_child(Button::new("Clear").on_click(|_ctx, data: &mut AppData, _env| data.clear_field()))
+        .with_spacer(10.0)
+        .with_child(Checkbox::new("Stop at").lens(AppData::stop_at_limit))
+        .with_spacer(5.0)
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("{}", data.anim_data.n_max)))
+        .with_child(Stepper::new().with_range(0.0, 1e7).with_step(100.0)
+            .lens(AppData::anim_data.then(SimulationData::n_max).map(|n_max| *n_max as f64, |n_max, value| *n_max = value as u32))
+            .controller(EditLink(|sim| Edit::StepLimit(sim.n_max))))
+        .with_spacer(10.0)
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("Steps/frame {}", data.steps_per_frame)).with_text_size(12.0))
+        .with_child(Stepper::new().with_range(1.0, 1000.0).with_step(1.0).lens(AppData::steps_per_frame))
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("FPS {:.0}", data.frame_rate)).with_text_size(12.0))
+        .with_child(Stepper::new().with_range(1.0, 60.0).with_step(1.0).lens(AppData::frame_rate))
+        .with_spacer(30.0)
+        .with_child(Label::new("Scenario").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Button::new("Load").on_click(|ctx, data: &mut AppData, _env| {
+            data.file_action = FileAction::LoadScenario;
+            let options = FileDialogOptions::new().allowed_types(vec![SCENARIO_FILE]);
+            ctx.submit_command(Command::new(commands::SHOW_OPEN_PANEL, options), None);
+        }))
+        .with_spacer(10.0)
+        .with_child(Button::new("Save").on_click(|ctx, data: &mut AppData, _env| {
+            data.file_action = FileAction::SaveScenario;
+            let options = FileDialogOptions::new().allowed_types(vec![SCENARIO_FILE]).default_type(SCENARIO_FILE);
+            ctx.submit_command(Command::new(commands::SHOW_SAVE_PANEL, options), None);
+        }))
+        .with_spacer(30.0)
+        .with_child(Label::new("Checkpoint").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Button::new("Resume").on_click(|ctx, data: &mut AppData, _env| {
+            data.file_action = FileAction::LoadCheckpoint;
+            let options = FileDialogOptions::new().allowed_types(vec![CHECKPOINT_FILE]);
+            ctx.submit_command(Command::new(commands::SHOW_OPEN_PANEL, options), None);
+        }))
+        .with_spacer(10.0)
+        .with_child(Button::new("Save").on_click(|ctx, data: &mut AppData, _env| {
+            data.file_action = FileAction::SaveCheckpoint;
+            let options = FileDialogOptions::new().allowed_types(vec![CHECKPOINT_FILE]).default_type(CHECKPOINT_FILE);
+            ctx.submit_command(Command::new(commands::SHOW_SAVE_PANEL, options), None);
+        }))
+        .with_spacer(30.0)
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("Frame scale {}", data.frame_scale)).with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Stepper::new().with_range(1.0, 16.0).with_step(1.0).lens(AppData::frame_scale))
+        .with_spacer(10.0)
+        .with_child(Button::new("Save frame").on_click(|ctx, data: &mut AppData, _env| {
+            data.file_action = FileAction::SaveFrame;
+            let options = FileDialogOptions::new().allowed_types(vec![PNG_FILE]).default_type(PNG_FILE);
+            ctx.submit_command(Command::new(commands::SHOW_SAVE_PANEL, options), None);
+        }))
+        .with_spacer(30.0)
+        .with_child(Label::new(|data: &AppData, _env: &Env| format!("Every {} steps", data.record_every)).with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Stepper::new().with_range(1.0, 100.0).with_step(1.0).lens(AppData::record_every))
+        .with_spacer(10.0)
+        .with_child(Either::new(|data: &AppData, _env| data.recording,
+            Button::new("Stop recording").on_click(|_ctx, data: &mut AppData, _env| {
+                data.recording = false;
+            }),
+            Button::new("Record").on_click(|ctx, data: &mut AppData, _env| {
+                data.file_action = FileAction::Record;
+                let options = FileDialogOptions::new().allowed_types(vec![GIF_FILE, PNG_FILE]).default_type(GIF_FILE);
+                ctx.submit_command(Command::new(commands::SHOW_SAVE_PANEL, options), None);
+            })))
+        .with_spacer(10.0)
+        .with_child(Either::new(|data: &AppData, _env| data.exporting,
+            Button::new("Stop export").on_click(|_ctx, data: &mut AppData, _env| {
+                data.exporting = false;
+            }),
+            Button::new("Export series").on_click(|ctx, data: &mut AppData, _env| {
+                data.file_action = FileAction::ExportSeries;
+                let options = FileDialogOptions::new().allowed_types(vec![CSV_FILE, NPY_FILE]).default_type(NPY_FILE);
+                ctx.submit_command(Command::new(commands::SHOW_SAVE_PANEL, options), None);
+            })))
+        .with_spacer(10.0)
+        .with_child(Button::new("Export field").on_click(|ctx, data: &mut AppData, _env| {
+            data.file_action = FileAction::ExportField;
+            let options = FileDialogOptions::new().allowed_types(vec![CSV_FILE, NPY_FILE]).default_type(NPY_FILE);
+            ctx.submit_command(Command::new(commands::SHOW_SAVE_PANEL, options), None);
+        }))
+        .with_spacer(10.0)
+        .with_child(Button::new("Export probes").on_click(|ctx, data: &mut AppData, _env| {
+            data.file_action = FileAction::ExportProbes;
+            let options = FileDialogOptions::new().allowed_types(vec![CSV_FILE]).default_type(CSV_FILE);
+            ctx.submit_command(Command::new(commands::SHOW_SAVE_PANEL, options), None);
+        }))
+        .with_spacer(10.0)
+        .with_child(Button::new("Export energy").on_click(|ctx, data: &mut AppData, _env| {
+            data.file_action = FileAction::ExportEnergy;
+            let options = FileDialogOptions::new().allowed_types(vec![CSV_FILE]).default_type(CSV_FILE);
+            ctx.submit_command(Command::new(commands::SHOW_SAVE_PANEL, options), None);
+        }))
+        .with_spacer(30.0)
+        .with_child(Label::new("Window Size").with_text_size(12.0))
+        .with_spacer(10.0)
+        .with_child(Slider::new().with_range(100.0, 1000.0).lens(AppData::anim_height))
+        .with_spacer(10.0)
+        .with_child(Checkbox::new("Smooth").lens(AppData::smooth_shading))
+        .with_spacer(10.0)
+        .with_child(Checkbox::new("Walls").lens(AppData::show_walls))
+        .with_flex_spacer(1.0)
+        .with_child(flex_button)
+        .background(Color::rgb8(20, 20, 20));
+
+    let cursor_window = LiveCursor{
+        punkt: Point{x: 100.0, y: 100.0},
+        cell_ratio: 1.0,
+        renderer: FieldRenderer::new()};
+
+    let simu_window = SimulationWidget{
+        cell_ratio: 1.0,
+        renderer: FieldRenderer::new()};
+
+    let anim_window = Flex::column()
+        .with_child(Either::new(|data, _env| data.edit_active,
+            Padding::new(20.0, cursor_window).background(Color::rgb8(104, 104, 104)),
+            Padding::new(20.0, simu_window).background(Color::rgb8(104, 104, 104))
+        ))
+        .with_child(Either::new(|data: &AppData, _env| data.anim_data.probes.is_empty(),
+            SizedBox::empty(),
+            Flex::column()
+                .with_child(Padding::new((20.0, 0.0, 20.0, 10.0), ProbePlot))
+                .with_child(Label::new(|data: &AppData, _env: &Env| spectrum_label(data)).with_text_size(12.0))
+                .with_child(Padding::new((20.0, 10.0, 20.0, 20.0), SpectrumPlot{peaks: Vec::new()}))
+                .background(Color::rgb8(104, 104, 104))))
+        .with_child(Either::new(|data: &AppData, _env| data.show_energy,
+            Flex::column()
+                .with_child(Padding::new((20.0, 0.0, 20.0, 10.0), EnergyPlot))
+                .with_child(Padding::new((20.0, 0.0, 20.0, 20.0), Label::new(|data: &AppData, _env: &Env| energy_label(data)).with_text_size(12.0)))
+                .background(Color::rgb8(104, 104, 104)),
+            SizedBox::empty()))
+        .with_flex_spacer(0.0);
+    
+    
+    Flex::row()
+        .with_child(button_bar.fix_width(100.0))
+        .with_spacer(1.0)
+        .with_flex_child(anim_window, 1.0)
+        .background(Color::rgb8(10, 10, 10))
+        .controller(SolverLink)
+}
+
+/// Opens the window with the shape, maps and parameters found in the data folder
+///
+/// Returns the exit code, 1 if the shape or the config cannot be loaded
+pub fn run(head_path: &str, cmap_path: &str) -> i32 {
+    // Loading txt file with vertices data
+    let mut input_path_temp: String = String::from(head_path);
+    input_path_temp.push_str("/data/shapes/edges_data.txt");
+    let mut shape: Shape = match Shape::load(&input_path_temp) {
+        Ok(shape) => shape,
+        Err(error) => {
+            eprintln!("{}", error);
+            return 1
+        }
+    };
+    // Using the wave speed and damping maps if there are any
+    let mut speed_path_temp: String = String::from(head_path);
+    speed_path_temp.push_str("/data/shapes/speed_data.txt");
+    if std::path::Path::new(&speed_path_temp).exists() {
+        shape.speed_path = Some(speed_path_temp);
+    }
+    let mut damping_path_temp: String = String::from(head_path);
+    damping_path_temp.push_str("/data/shapes/damping_data.txt");
+    if std::path::Path::new(&damping_path_temp).exists() {
+        shape.damping_path = Some(damping_path_temp);
+    }
+    // Loading the simulation parameters
+    let mut config_path_temp: String = String::from(head_path);
+    config_path_temp.push_str("/data/config.txt");
+    let params: SimulationParams = if std::path::Path::new(&config_path_temp).exists() {
+        match SimulationParams::load(&config_path_temp) {
+            Ok(params) => params,
+            Err(error) => {
+                eprintln!("{}", error);
+                return 1
+            }
+        }
+    } else {
+        SimulationParams::default()
+    };
+
+    let anim_data = SimulationData::new(Arc::new(shape), params, Arc::new(functions::get_cmap(cmap_path)));
+    let params_edit: SimulationParams = anim_data.params;
+
+    let window = WindowDesc::new(build_ui);
+
+    let launcher = AppLauncher::with_window(window);
+    // The eigenmodes and the frames of the solver are handed back from their threads through this sink
+    let sink: ExtEventSink = launcher.get_external_handle();
+    let solver: Solver = Solver::start(sink.clone());
+    solver.load(anim_data.clone());
+    launcher
+        .delegate(Delegate{sink})
+        .launch(AppData {
+            edit_active: true,
+            cc_size: 10.0*params.spacing,
+            anim_data,
+            frame_rate: 20.0,
+            steps_per_frame: 1.0,
+            steps_per_second: 0.0,
+            anim_paused: true,
+            solver,
+            solver_changes: 1,
+            step_count: 10.0,
+            stop_at_limit: false,
+            anim_height: 700.0,
+            radio_status: LiveCursorRadio::Gauss,
+            initial_strength: 1.0,
+            paint_speed: params.speed,
+            paint_damping: 1.0,
+            source_kind: SourceKind::Sine,
+            source_frequency: 1.0,
+            params_edit,
+            cmap_path: String::from(cmap_path),
+            file_action: FileAction::LoadScenario,
+            frame_scale: 1.0,
+            recording: false,
+            record_path: String::new(),
+            record_every: 1.0,
+            exporting: false,
+            export_path: String::new(),
+            spectrum_probe: 0.0,
+            spectrum_window: Window::Hann,
+            spectrum: None,
+            modes: Arc::new(Modes::empty()),
+            mode_count: 6.0,
+            modes_computing: false,
+            mode_index: 0.0,
+            show_mode: false,
+            show_energy: false,
+            smooth_shading: false,
+            show_walls: false})
+        .expect("launch failed");
+    0
+}
@@ -0,0 +1,102 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+use crate::cell::CellMask;
+use crate::field::HexField;
+use crate::functions;
+
+/// Coordinates of a cell painted by hand and the value of the map there
+pub type PaintedCell = (f64, f64, f64);
+
+/// Returns the values of the regions of a file and for every cell the number
+/// of the region it lies in
+///
+/// Every line of the file holds an edge and the value of the region it
+/// belongs to: "x1 y1 x2 y2 value". All edges sharing the same value form the
+/// outline of one region (several closed polygons are allowed). Regions are
+/// numbered in the order of the file, later regions are drawn on top of
+/// earlier ones.
+///
+/// - x_off, y_off: range x left and range y down of the grid
+pub fn region_map(path: &str, x_len: usize, y_len: usize, x_off: usize, y_off: usize, spacing: f64) -> (Vec<f64>, HexField<Option<u8>>) {
+    let data_array: Vec<Vec<String>> = functions::csv_parse(path, ' ');
+    // Grouping the edges by their value, keeping the order of the file
+    let mut values: Vec<f64> = Vec::new();
+    let mut regions: Vec<[Vec<f64>; 4]> = Vec::new();
+    for line in data_array.iter().filter(|line| line.len() >= 5) {
+        let value: f64 = f64::from_str(&line[4]).unwrap();
+        let index: usize = match values.iter().position(|&v| v == value) {
+            Some(index) => index,
+            None => {
+                values.push(value);
+                regions.push([Vec::new(), Vec::new(), Vec::new(), Vec::new()]);
+                values.len() - 1
+            }
+        };
+        for column in 0..4 {
+            regions[index][column].push(f64::from_str(&line[column]).unwrap());
+        }
+    }
+    // Filling the map row by row like the hex grid itself
+    let mut map: HexField<Option<u8>> = HexField::new(x_len, y_len, None);
+    for (index, edges) in regions.iter().enumerate() {
+        let id: u8 = u8::try_from(index).expect("Too many regions in one file, at most 256 are supported");
+        let [x1, y1, x2, y2] = edges;
+        for i_y in 0..y_len {
+            let (_x, y) = functions::get_cord(0, i_y, x_off, y_off, spacing);
+            let inter = functions::get_vec_intersect(y, x1, y1, x2, y2);
+            for i_x in 0..x_len {
+                let (x, _y) = functions::get_cord(i_x, i_y, x_off, y_off, spacing);
+                if functions::amount_bigger(x, &inter)%2 == 1 {
+                    map[i_y][i_x] = Some(id);
+                }
+            }
+        }
+    }
+    (values, map)
+}
+
+/// Returns a per cell field with the value of the region of every cell, cells
+/// outside of all regions take the default
+pub fn fill_regions(values: &[f64], map: &HexField<Option<u8>>, default: f64) -> HexField {
+    let mut field: HexField = HexField::new(map.x_len(), map.y_len(), default);
+    for i_y in 0..map.y_len() {
+        for i_x in 0..map.x_len() {
+            if let Some(id) = map[i_y][i_x] {
+                field[i_y][i_x] = values[id as usize];
+            }
+        }
+    }
+    field
+}
+
+/// Returns a per cell field filled from the regions of a file, see region_map
+pub fn load_regions(path: &str, x_len: usize, y_len: usize, x_off: usize, y_off: usize, spacing: f64, default: f64) -> HexField {
+    let (values, map) = region_map(path, x_len, y_len, x_off, y_off, spacing);
+    fill_regions(&values, &map, default)
+}
+
+/// Sets all cells of a field within a radius around a cell to a value
+///
+/// - radius: radius of the brush in units of the coordinates
+pub fn paint_disc(field: &mut HexField, x_pos: usize, y_pos: usize, radius: f64, spacing: f64, value: f64) {
+    for i_y in 0..field.y_len() {
+        for i_x in 0..field.x_len() {
+            if functions::grid_distance(x_pos, y_pos, i_x, i_y, 10, 10, spacing) <= radius {
+                field[i_y][i_x] = value;
+            }
+        }
+    }
+}
+
+/// Returns the largest value of a field on the cells inside the shape
+pub fn max_inside(field: &HexField, hex_grid: &CellMask) -> f64 {
+    let mut current_max: f64 = 0.0;
+    for i_y in 0..field.y_len() {
+        for i_x in 0..field.x_len() {
+            if hex_grid[i_y][i_x].is_active() && field[i_y][i_x] > current_max {
+                current_max = field[i_y][i_x];
+            }
+        }
+    }
+    current_max
+}
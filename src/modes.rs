@@ -0,0 +1,531 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use crate::cell::CellMask;
+use crate::field::HexField;
+use crate::functions;
+use crate::params::SimulationParams;
+
+/// Relative residual the linear solves are run down to
+const CG_TOLERANCE: f64 = 1e-8;
+/// Relative residual at which a mode counts as converged
+const MODE_TOLERANCE: f64 = 1e-6;
+/// Upper limit of Lanczos vectors per requested mode
+const STEPS_PER_MODE: usize = 12;
+/// Number of vectors of a Lanczos block, at least the number of modes sharing
+/// a frequency, which is two on the symmetric shapes of the hex lattice
+const BLOCK_SIZE: usize = 2;
+/// Share of its length a new Lanczos vector has to keep after the
+/// orthogonalisation, shorter ones are dropped from the block
+const DEFLATION: f64 = 1e-10;
+/// Size below which the coarsest level of the multigrid is solved directly
+const COARSEST_SIZE: usize = 400;
+/// Factor on the coarse corrections of the multigrid, merging cells into groups
+/// gives corrections that are too small, which this makes up for
+const OVERCORRECTION: f64 = 1.8;
+
+/// Lowest eigenmodes of the wave equation on the grid of a simulation
+pub struct Modes {
+    /// Frequency of every mode in the leapfrog scheme, in 1/time unit
+    pub frequencies: Vec<f64>,
+    /// Displacement of every mode, scaled to a largest magnitude of 1
+    pub shapes: Vec<HexField>,
+}
+
+impl Modes {
+    pub fn empty() -> Modes {
+        Modes{frequencies: Vec::new(), shapes: Vec::new()}
+    }
+}
+
+/// Symmetric sparse matrix stored by rows
+struct Sparse {
+    starts: Vec<usize>,
+    columns: Vec<usize>,
+    values: Vec<f64>,
+    diagonal: Vec<f64>,
+}
+
+impl Sparse {
+    /// Sets up the matrix from the entries of every row, entries with the same
+    /// column are summed
+    fn from_rows(rows: Vec<Vec<(usize, f64)>>) -> Sparse {
+        let mut matrix = Sparse{starts: vec![0], columns: Vec::new(), values: Vec::new(), diagonal: Vec::new()};
+        for (i, mut row) in rows.into_iter().enumerate() {
+            row.sort_by_key(|&(column, _)| column);
+            let mut diagonal: f64 = 0.0;
+            for (column, value) in row {
+                if column == i {
+                    diagonal += value;
+                }
+                if matrix.columns.len() > matrix.starts[i] && *matrix.columns.last().unwrap() == column {
+                    *matrix.values.last_mut().unwrap() += value;
+                } else {
+                    matrix.columns.push(column);
+                    matrix.values.push(value);
+                }
+            }
+            matrix.diagonal.push(diagonal);
+            matrix.starts.push(matrix.columns.len());
+        }
+        matrix
+    }
+
+    fn len(&self) -> usize {
+        self.diagonal.len()
+    }
+
+    fn row(&self, i: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let range = self.starts[i]..self.starts[i + 1];
+        self.columns[range.clone()].iter().cloned().zip(self.values[range].iter().cloned())
+    }
+
+    fn apply(&self, x: &[f64], result: &mut [f64]) {
+        for (i, entry) in result.iter_mut().enumerate() {
+            *entry = self.row(i).map(|(j, value)| value*x[j]).sum();
+        }
+    }
+
+    /// One Gauss-Seidel sweep on A x = b, forwards or backwards through the rows
+    fn gauss_seidel(&self, b: &[f64], x: &mut [f64], forward: bool) {
+        for k in 0..self.len() {
+            let i: usize = if forward {k} else {self.len() - 1 - k};
+            let off_diagonal: f64 = self.row(i).filter(|&(j, _)| j != i).map(|(j, value)| value*x[j]).sum();
+            x[i] = (b[i] - off_diagonal)/self.diagonal[i];
+        }
+    }
+}
+
+/// Level of the multigrid preconditioner, the unknowns of the next coarser
+/// level are sums over small groups of neighbouring unknowns of this one
+struct Level {
+    matrix: Sparse,
+    /// Index of the group of every unknown on the next level
+    groups: Vec<usize>,
+}
+
+/// Preconditioner for the conjugate gradients, a symmetric V-cycle of an
+/// aggregation multigrid built from the matrix alone
+///
+/// Groups of 2x2 cells of the hex grid are merged into the unknowns of the
+/// next level, which works for any shape and speed map without knowing the
+/// geometry.
+struct Multigrid {
+    levels: Vec<Level>,
+    /// Cholesky factor of the coarsest matrix, stored by rows
+    coarsest: Vec<Vec<f64>>,
+}
+
+impl Multigrid {
+    fn new(matrix: Sparse, cells: &[(usize, usize)]) -> Multigrid {
+        let mut levels: Vec<Level> = Vec::new();
+        let mut matrix: Sparse = matrix;
+        let mut positions: Vec<(usize, usize)> = cells.to_vec();
+        while matrix.len() > COARSEST_SIZE {
+            // Grouping the unknowns by their position halved
+            let mut numbers: HashMap<(usize, usize), usize> = HashMap::new();
+            let mut groups: Vec<usize> = Vec::with_capacity(matrix.len());
+            let mut coarse_positions: Vec<(usize, usize)> = Vec::new();
+            for &(x, y) in positions.iter() {
+                let next: usize = numbers.len();
+                let group: usize = *numbers.entry((x/2, y/2)).or_insert(next);
+                if group == coarse_positions.len() {
+                    coarse_positions.push((x/2, y/2));
+                }
+                groups.push(group);
+            }
+            if coarse_positions.len() == matrix.len() {
+                break;
+            }
+            // Galerkin product, the coarse entries sum the fine ones between the groups
+            let mut rows: Vec<Vec<(usize, f64)>> = vec![Vec::new(); coarse_positions.len()];
+            for i in 0..matrix.len() {
+                for (j, value) in matrix.row(i) {
+                    rows[groups[i]].push((groups[j], value));
+                }
+            }
+            let coarse: Sparse = Sparse::from_rows(rows);
+            levels.push(Level{matrix, groups});
+            matrix = coarse;
+            positions = coarse_positions;
+        }
+        // Factorising the coarsest level
+        let n: usize = matrix.len();
+        let mut factor: Vec<Vec<f64>> = vec![vec![0.0; n]; n];
+        for (i, row) in factor.iter_mut().enumerate() {
+            for (j, value) in matrix.row(i) {
+                row[j] = value;
+            }
+        }
+        for j in 0..n {
+            let pivot: f64 = (factor[j][j] - (0..j).map(|k| factor[j][k].powi(2)).sum::<f64>()).sqrt();
+            factor[j][j] = pivot;
+            for i in j + 1..n {
+                let sum: f64 = (0..j).map(|k| factor[i][k]*factor[j][k]).sum();
+                factor[i][j] = (factor[i][j] - sum)/pivot;
+            }
+            for row in factor.iter_mut().take(j) {
+                row[j] = 0.0;
+            }
+        }
+        levels.push(Level{matrix, groups: Vec::new()});
+        Multigrid{levels, coarsest: factor}
+    }
+
+    /// Returns an approximation of A^-1 b on the given level
+    fn cycle(&self, level: usize, b: &[f64]) -> Vec<f64> {
+        let matrix: &Sparse = &self.levels[level].matrix;
+        let n: usize = matrix.len();
+        if level + 1 == self.levels.len() {
+            // Forward and backward substitution with the Cholesky factor
+            let l: &Vec<Vec<f64>> = &self.coarsest;
+            let mut y: Vec<f64> = vec![0.0; n];
+            for i in 0..n {
+                y[i] = (b[i] - (0..i).map(|k| l[i][k]*y[k]).sum::<f64>())/l[i][i];
+            }
+            for i in (0..n).rev() {
+                y[i] = (y[i] - (i + 1..n).map(|k| l[k][i]*y[k]).sum::<f64>())/l[i][i];
+            }
+            return y
+        }
+        let groups: &Vec<usize> = &self.levels[level].groups;
+        let mut x: Vec<f64> = vec![0.0; n];
+        matrix.gauss_seidel(b, &mut x, true);
+        // Restricting the residual, solving for the correction and adding it back
+        let mut residual: Vec<f64> = vec![0.0; n];
+        matrix.apply(&x, &mut residual);
+        let mut coarse_b: Vec<f64> = vec![0.0; self.levels[level + 1].matrix.len()];
+        for i in 0..n {
+            coarse_b[groups[i]] += b[i] - residual[i];
+        }
+        let correction: Vec<f64> = self.cycle(level + 1, &coarse_b);
+        for i in 0..n {
+            x[i] += OVERCORRECTION*correction[groups[i]];
+        }
+        matrix.gauss_seidel(b, &mut x, false);
+        x
+    }
+}
+
+/// The wave operator restricted to the cells inside the shape
+///
+/// With the speed c_i of every cell the simulation solves u_tt = -C L u, where
+/// L is the hex Laplacian of calc_next_frame with the walls held at zero and
+/// C = diag(c_i^2). C L is not symmetric, so the solver works with the similar
+/// symmetric matrix B = C^1/2 L C^1/2, whose eigenvectors give the modes of
+/// C L after a multiplication with C^1/2.
+struct Operator {
+    cells: Vec<(usize, usize)>,
+    speeds: Vec<f64>,
+    multigrid: Multigrid,
+}
+
+impl Operator {
+    fn new(hex_grid: &CellMask, hex_speed: &HexField, spacing: f64) -> Operator {
+        // Numbering the active cells
+        let mut index: HexField<Option<usize>> = HexField::new(hex_grid.x_len(), hex_grid.y_len(), None);
+        let mut cells: Vec<(usize, usize)> = Vec::new();
+        for i_y in 0..hex_grid.y_len() {
+            for i_x in 0..hex_grid.x_len() {
+                if hex_grid[i_y][i_x].is_active() {
+                    index[i_y][i_x] = Some(cells.len());
+                    cells.push((i_x, i_y));
+                }
+            }
+        }
+        let speeds: Vec<f64> = cells.iter().map(|&(i_x, i_y)| hex_speed[i_y][i_x]).collect();
+        // Every row holds 2/3 h^-2 c_i (6 c_i u_i - sum of c_j u_j over the neighbours inside)
+        let factor: f64 = 2.0/3.0/spacing.powi(2);
+        let rows: Vec<Vec<(usize, f64)>> = cells.iter().enumerate().map(|(i, &(i_x, i_y))| {
+            let mut row: Vec<(usize, f64)> = vec![(i, 6.0*factor*speeds[i]*speeds[i])];
+            for &(n_y, n_x) in functions::neighbour_indices(i_y, i_x).iter() {
+                if let Some(j) = index[n_y][n_x] {
+                    row.push((j, -factor*speeds[i]*speeds[j]));
+                }
+            }
+            row
+        }).collect();
+        let multigrid: Multigrid = Multigrid::new(Sparse::from_rows(rows), &cells);
+        Operator{cells, speeds, multigrid}
+    }
+
+    fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Solves B x = b with conjugate gradients preconditioned by the multigrid,
+    /// B is positive definite since the walls are held at zero
+    fn solve(&self, b: &[f64]) -> Vec<f64> {
+        let matrix: &Sparse = &self.multigrid.levels[0].matrix;
+        let n: usize = self.len();
+        let mut x: Vec<f64> = vec![0.0; n];
+        let mut r: Vec<f64> = b.to_vec();
+        let mut z: Vec<f64> = self.multigrid.cycle(0, &r);
+        let mut p: Vec<f64> = z.clone();
+        let mut q: Vec<f64> = vec![0.0; n];
+        let mut rz: f64 = dot(&r, &z);
+        let limit: f64 = CG_TOLERANCE.powi(2)*dot(&r, &r);
+        for _ in 0..n {
+            if dot(&r, &r) <= limit {
+                break;
+            }
+            matrix.apply(&p, &mut q);
+            let alpha: f64 = rz/dot(&p, &q);
+            for i in 0..n {
+                x[i] += alpha*p[i];
+                r[i] -= alpha*q[i];
+            }
+            z = self.multigrid.cycle(0, &r);
+            let rz_new: f64 = dot(&r, &z);
+            let beta: f64 = rz_new/rz;
+            rz = rz_new;
+            for i in 0..n {
+                p[i] = z[i] + beta*p[i];
+            }
+        }
+        x
+    }
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b.iter()).map(|(x, y)| x*y).sum()
+}
+
+/// Returns the eigenvalues and eigenvectors (as columns) of a small symmetric
+/// matrix with the cyclic Jacobi method
+fn symmetric_eigen(matrix: &[Vec<f64>]) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let n: usize = matrix.len();
+    let mut a: Vec<Vec<f64>> = matrix.to_vec();
+    let mut v: Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| if i == j {1.0} else {0.0}).collect()).collect();
+    for _sweep in 0..100 {
+        let off: f64 = (0..n).flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j))).map(|(i, j)| a[i][j].powi(2)).sum();
+        let total: f64 = (0..n).map(|i| a[i][i].powi(2)).sum::<f64>() + off;
+        if off <= 1e-30*total {
+            break;
+        }
+        for p in 0..n {
+            for q in p + 1..n {
+                if a[p][q] == 0.0 {
+                    continue;
+                }
+                // Rotation zeroing the entries p, q and q, p
+                let theta: f64 = (a[q][q] - a[p][p])/(2.0*a[p][q]);
+                let t: f64 = if theta == 0.0 {1.0} else {theta.signum()/(theta.abs() + (theta*theta + 1.0).sqrt())};
+                let c: f64 = 1.0/(t*t + 1.0).sqrt();
+                let s: f64 = t*c;
+                for row in a.iter_mut().chain(v.iter_mut()) {
+                    let (a_kp, a_kq) = (row[p], row[q]);
+                    row[p] = c*a_kp - s*a_kq;
+                    row[q] = s*a_kp + c*a_kq;
+                }
+                let (upper, lower) = a.split_at_mut(q);
+                for (a_pk, a_qk) in upper[p].iter_mut().zip(lower[0].iter_mut()) {
+                    let (old_p, old_q) = (*a_pk, *a_qk);
+                    *a_pk = c*old_p - s*old_q;
+                    *a_qk = s*old_p + c*old_q;
+                }
+            }
+        }
+    }
+    ((0..n).map(|i| a[i][i]).collect(), v)
+}
+
+/// Orthonormalises vectors that are already orthogonal to the Lanczos basis
+///
+/// Vectors that lose all but a tiny share of their length are linearly
+/// dependent on the others and dropped, which deflates the block.
+fn orthonormalise(vectors: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let mut block: Vec<Vec<f64>> = Vec::new();
+    for vector in vectors {
+        let mut w: Vec<f64> = vector.clone();
+        let length: f64 = dot(&w, &w).sqrt();
+        for _ in 0..2 {
+            for vector in block.iter() {
+                let projection: f64 = dot(&w, vector);
+                w.iter_mut().zip(vector.iter()).for_each(|(value, v)| *value -= projection*v);
+            }
+        }
+        let norm: f64 = dot(&w, &w).sqrt();
+        if norm > DEFLATION*length {
+            w.iter_mut().for_each(|value| *value /= norm);
+            block.push(w);
+        }
+    }
+    block
+}
+
+/// Computes the count lowest eigenmodes of the shape with reflecting walls
+///
+/// Runs the block Lanczos method on the inverse of the wave operator
+/// (shift-invert around zero), which turns the lowest modes into the best
+/// separated ones. The block holds several vectors so that modes sharing a
+/// frequency, as on symmetric shapes, are all found. Damping, the absorbing
+/// layer and the boundary types of the edges are not part of the operator,
+/// every wall holds the field at zero. The frequencies are those of the
+/// leapfrog scheme with the time step of the parameters, so a simulation
+/// started from a mode oscillates at exactly that frequency.
+pub fn solve(hex_grid: &CellMask, hex_speed: &HexField, params: &SimulationParams, count: usize) -> Modes {
+    let operator = Operator::new(hex_grid, hex_speed, params.spacing);
+    let n: usize = operator.len();
+    let count: usize = count.min(n);
+    if count == 0 {
+        return Modes::empty()
+    }
+    let block_size: usize = BLOCK_SIZE.min(n);
+    let max_size: usize = (STEPS_PER_MODE*count + 20).max(count + block_size).min(n);
+    // Lanczos vectors, kept for the full reorthogonalisation and the Ritz vectors,
+    // and the inverse operator projected onto them
+    let mut basis: Vec<Vec<f64>> = Vec::new();
+    let mut projected: Vec<Vec<f64>> = vec![vec![0.0; max_size]; max_size];
+    // Starting from uneven vectors that have a share of every mode
+    let start: Vec<Vec<f64>> = (0..block_size).map(|b| operator.cells.iter()
+        .map(|&(i_x, i_y)| 1.0 + (((i_x*73856093) ^ (i_y*19349663) ^ ((b + 1)*83492791))%1000) as f64/1000.0).collect()).collect();
+    let mut block: Vec<Vec<f64>> = orthonormalise(&start);
+    let mut ritz: (Vec<f64>, Vec<Vec<f64>>) = (Vec::new(), Vec::new());
+    while !block.is_empty() {
+        let first: usize = basis.len();
+        basis.extend(block);
+        let m: usize = basis.len();
+        // Full reorthogonalisation against all vectors so far, twice for safety,
+        // the projections are the entries of the projected operator
+        let mut residuals: Vec<Vec<f64>> = Vec::new();
+        for j in first..m {
+            let mut w: Vec<f64> = operator.solve(&basis[j]);
+            for _ in 0..2 {
+                for (i, vector) in basis.iter().enumerate() {
+                    let projection: f64 = dot(&w, vector);
+                    w.iter_mut().zip(vector.iter()).for_each(|(value, v)| *value -= projection*v);
+                    // Entries within the block are computed from both sides
+                    let share: f64 = if i < first {projection} else {0.5*projection};
+                    projected[i][j] += share;
+                    projected[j][i] += share;
+                }
+            }
+            residuals.push(w);
+        }
+        // The next block is empty once the basis spans an invariant subspace
+        let mut next: Vec<Vec<f64>> = orthonormalise(&residuals);
+        next.truncate(max_size - m);
+        // Checking the Ritz pairs for convergence, the residual of a pair is the
+        // part of its image outside the basis, which only the last block adds
+        if m >= count || next.is_empty() {
+            let matrix: Vec<Vec<f64>> = projected[..m].iter().map(|row| row[..m].to_vec()).collect();
+            let (values, vectors) = symmetric_eigen(&matrix);
+            let mut order: Vec<usize> = (0..m).collect();
+            order.sort_by(|&a, &b| values[b].total_cmp(&values[a]));
+            order.truncate(count);
+            let converged: bool = order.iter().all(|&k| {
+                let mut residual: Vec<f64> = vec![0.0; n];
+                for (j, w) in residuals.iter().enumerate() {
+                    let coefficient: f64 = vectors[first + j][k];
+                    residual.iter_mut().zip(w.iter()).for_each(|(value, w_i)| *value += coefficient*w_i);
+                }
+                dot(&residual, &residual).sqrt() <= MODE_TOLERANCE*values[k].abs()
+            });
+            ritz = (order.iter().map(|&k| values[k]).collect(),
+                order.iter().map(|&k| (0..m).map(|i| vectors[i][k]).collect()).collect());
+            if converged {
+                break;
+            }
+        }
+        block = next;
+    }
+
+    // The eigenvalues of B are the inverses of the Ritz values
+    let dt: f64 = params.time_spacing;
+    let mut modes: Modes = Modes::empty();
+    for (theta, coefficients) in ritz.0.iter().zip(ritz.1.iter()) {
+        let eigenvalue: f64 = 1.0/theta;
+        // u(t) = cos(omega t) u(0) solves the leapfrog scheme if 2 - 2 cos(omega dt) = dt^2 eigenvalue
+        let omega: f64 = (1.0 - 0.5*dt*dt*eigenvalue).max(-1.0).acos()/dt;
+        let mut shape: HexField = HexField::new(hex_grid.x_len(), hex_grid.y_len(), 0.0);
+        for (i, &(i_x, i_y)) in operator.cells.iter().enumerate() {
+            let value: f64 = coefficients.iter().zip(basis.iter()).map(|(c, vector)| c*vector[i]).sum();
+            shape[i_y][i_x] = operator.speeds[i]*value;
+        }
+        // Scaling to a largest magnitude of 1 with a positive extremum
+        let extremum: f64 = operator.cells.iter().map(|&(i_x, i_y)| shape[i_y][i_x])
+            .fold(0.0, |max: f64, value| if value.abs() > max.abs() {value} else {max});
+        if extremum != 0.0 {
+            for &(i_x, i_y) in operator.cells.iter() {
+                shape[i_y][i_x] /= extremum;
+            }
+        }
+        modes.frequencies.push(omega/(2.0*PI));
+        modes.shapes.push(shape);
+    }
+    modes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cell::CellKind;
+
+    /// Returns a grid with a hexagon of cells of the given radius in its middle,
+    /// which has the sixfold symmetry of the lattice
+    fn hexagon(radius: i64) -> CellMask {
+        let size: usize = 2*radius as usize + 5;
+        let mut hex_grid: CellMask = HexField::new(size, size, CellKind::Outside);
+        let centre: (i64, i64) = (size as i64/2, size as i64/2);
+        // Axial coordinates of a cell, odd rows are shifted to the right
+        let axial = |i_x: i64, i_y: i64| (i_x - (i_y - (i_y & 1))/2, i_y);
+        let (q_0, r_0) = axial(centre.0, centre.1);
+        for i_y in 0..size {
+            for i_x in 0..size {
+                let (q, r) = axial(i_x as i64, i_y as i64);
+                let (d_q, d_r) = (q - q_0, r - r_0);
+                if (d_q.abs() + d_r.abs() + (d_q + d_r).abs())/2 <= radius {
+                    hex_grid[i_y][i_x] = CellKind::Interior;
+                }
+            }
+        }
+        hex_grid
+    }
+
+    /// Returns the frequencies of all modes from the dense operator, lowest first
+    fn dense_frequencies(hex_grid: &CellMask, hex_speed: &HexField, params: &SimulationParams) -> Vec<f64> {
+        let operator = Operator::new(hex_grid, hex_speed, params.spacing);
+        let matrix: &Sparse = &operator.multigrid.levels[0].matrix;
+        let dense: Vec<Vec<f64>> = (0..matrix.len()).map(|i| {
+            let mut row: Vec<f64> = vec![0.0; matrix.len()];
+            matrix.row(i).for_each(|(j, value)| row[j] = value);
+            row
+        }).collect();
+        let (mut values, _vectors) = symmetric_eigen(&dense);
+        values.sort_by(|a, b| a.total_cmp(b));
+        let dt: f64 = params.time_spacing;
+        values.iter().map(|eigenvalue| (1.0 - 0.5*dt*dt*eigenvalue).acos()/dt/(2.0*PI)).collect()
+    }
+
+    fn params() -> SimulationParams {
+        SimulationParams{speed: 1.0, spacing: 0.01, time_spacing: 0.001, threads: 1}
+    }
+
+    #[test]
+    fn finds_both_modes_of_a_degenerate_pair() {
+        let hex_grid: CellMask = hexagon(8);
+        let hex_speed: HexField = HexField::new(hex_grid.x_len(), hex_grid.y_len(), 1.0);
+        let expected: Vec<f64> = dense_frequencies(&hex_grid, &hex_speed, &params());
+        // The second and third mode of the hexagon share their frequency, as do the fourth and fifth
+        assert!((expected[1] - expected[2]).abs() < 1e-9*expected[1]);
+        assert!((expected[3] - expected[4]).abs() < 1e-9*expected[3]);
+        let modes: Modes = solve(&hex_grid, &hex_speed, &params(), 6);
+        assert_eq!(modes.frequencies.len(), 6);
+        for (frequency, expected) in modes.frequencies.iter().zip(expected.iter()) {
+            assert!((frequency - expected).abs() < 1e-6*expected, "{} instead of {}", frequency, expected);
+        }
+    }
+
+    #[test]
+    fn finds_all_modes_of_a_small_shape() {
+        // Seven cells, the Krylov space is exhausted after three blocks
+        let hex_grid: CellMask = hexagon(1);
+        let hex_speed: HexField = HexField::new(hex_grid.x_len(), hex_grid.y_len(), 1.0);
+        let expected: Vec<f64> = dense_frequencies(&hex_grid, &hex_speed, &params());
+        let modes: Modes = solve(&hex_grid, &hex_speed, &params(), 10);
+        assert_eq!(modes.frequencies.len(), 7);
+        for (frequency, expected) in modes.frequencies.iter().zip(expected.iter()) {
+            assert!((frequency - expected).abs() < 1e-6*expected, "{} instead of {}", frequency, expected);
+        }
+    }
+}
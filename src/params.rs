@@ -0,0 +1,137 @@
+use std::str::FromStr;
+#[cfg(feature = "gui")]
+use druid::{Data, Lens};
+use crate::functions;
+
+/// Most threads a run may ask for, more point at a corrupt file
+pub const MAX_THREADS: usize = 1024;
+
+/// Physical and numerical parameters of a simulation
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "gui", derive(Data, Lens))]
+pub struct SimulationParams {
+    /// Wave speed of cells without an entry in the speed map
+    pub speed: f64,
+    /// Distance between neighbouring cells
+    pub spacing: f64,
+    /// Length of a time step
+    pub time_spacing: f64,
+    /// Number of threads updating the field, 0 uses one per core
+    pub threads: usize,
+}
+
+impl SimulationParams {
+    /// Sine of 60 degrees, ratio of row distance to cell spacing
+    pub const SI60: f64 = 0.8660254037844386;
+    /// Cosine of 60 degrees, horizontal offset of odd rows in cells
+    pub const CO60: f64 = 0.5;
+
+    /// Reads parameters from a config file, missing entries keep their default
+    ///
+    /// Every line holds a key and a value separated by a space, the keys are
+    /// speed, spacing, time_spacing and threads. Errors name the file and the
+    /// line, empty lines are skipped.
+    pub fn load(path: &str) -> Result<SimulationParams, String> {
+        if let Err(error) = std::fs::metadata(path) {
+            return Err(format!("Could not load config {}: {}", path, error))
+        }
+        let mut params: SimulationParams = SimulationParams::default();
+        for (i, line) in functions::csv_parse(path, ' ').iter().enumerate() {
+            if line.iter().all(|entry| entry.trim().is_empty()) {
+                continue;
+            }
+            params.set(line).map_err(|error| format!("Could not load config {}: line {}: {}", path, i + 1, error))?;
+        }
+        Ok(params)
+    }
+
+    /// Sets the parameter of a line of a config file
+    fn set(&mut self, line: &[String]) -> Result<(), String> {
+        let key: &str = line[0].trim();
+        let value: &str = match line.get(1) {
+            Some(value) => value.trim(),
+            None => return Err(format!("{} has no value", key)),
+        };
+        if line.len() > 2 {
+            return Err(format!("{} has more than one value", key))
+        }
+        if key == "threads" {
+            self.threads = match usize::from_str(value) {
+                Ok(threads) if threads <= MAX_THREADS => threads,
+                _ => return Err(format!("threads has to be a whole number from 0 to {}, not {}", MAX_THREADS, value)),
+            };
+            return Ok(())
+        }
+        let number: f64 = f64::from_str(value).map_err(|_| format!("{} has to be a number, not {}", key, value))?;
+        match key {
+            "speed" => self.speed = number,
+            "spacing" => self.spacing = number,
+            "time_spacing" => self.time_spacing = number,
+            other => return Err(format!("unknown key {}", other)),
+        }
+        Ok(())
+    }
+
+    /// Returns the Courant number of a cell with the given speed
+    pub fn courant(&self, speed: f64) -> f64 {
+        speed*self.time_spacing/self.spacing
+    }
+
+    /// Returns the fastest wave speed the time step can handle
+    pub fn max_stable_speed(&self) -> f64 {
+        functions::CFL_LIMIT*self.spacing/self.time_spacing
+    }
+
+    /// Returns the parameters with the time step shortened if needed, so that
+    /// waves of the given speed stay stable
+    pub fn stabilized(&self, max_speed: f64) -> SimulationParams {
+        let mut params: SimulationParams = *self;
+        let max_time_spacing: f64 = functions::CFL_LIMIT*self.spacing/max_speed;
+        if self.time_spacing > max_time_spacing {
+            params.time_spacing = max_time_spacing;
+        }
+        params
+    }
+}
+
+impl Default for SimulationParams {
+    fn default() -> SimulationParams {
+        SimulationParams {
+            speed: 0.7,
+            spacing: 0.01,
+            time_spacing: 0.01,
+            threads: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the error of loading a config file with the given content
+    fn load_error(name: &str, content: &str) -> String {
+        let path: String = std::env::temp_dir().join(name).to_string_lossy().into_owned();
+        std::fs::write(&path, content).unwrap();
+        let error: String = SimulationParams::load(&path).err().expect("The config should have been refused");
+        std::fs::remove_file(&path).unwrap();
+        error
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let error: String = load_error("wave_bad_value.txt", "speed 0.7
+spacing x
+");
+        assert!(error.ends_with("line 2: spacing has to be a number, not x"), "{}", error);
+        let error: String = load_error("wave_bad_key.txt", "speed 0.7
+
+sped 0.5
+");
+        assert!(error.ends_with("line 3: unknown key sped"), "{}", error);
+        for threads in ["-1", "2.5", "100000"] {
+            let error: String = load_error("wave_bad_threads.txt", &format!("threads {}\n", threads));
+            assert!(error.ends_with(&format!("line 1: threads has to be a whole number from 0 to {}, not {}", MAX_THREADS, threads)), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+#[cfg(feature = "gui")]
+use druid::Data;
+use crate::field::HexField;
+use crate::spectrum::{self, Spectrum, Window};
+
+/// Most samples a recording keeps, older probe values are dropped and the
+/// energy series is thinned out, see EnergySeries::record
+pub const MAX_SAMPLES: usize = 65536;
+
+/// A microphone recording the field at one cell every time step
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "gui", derive(Data))]
+pub struct Probe {
+    pub i_x: usize,
+    pub i_y: usize,
+}
+
+/// Values recorded by the probes, one series per probe sharing the times
+///
+/// Only the last MAX_SAMPLES time steps are kept, so a long run neither grows
+/// without bound nor gets slower to copy.
+#[derive(Clone)]
+pub struct ProbeSeries {
+    pub times: VecDeque<f64>,
+    pub values: Vec<VecDeque<f64>>,
+}
+
+impl ProbeSeries {
+    pub fn new() -> ProbeSeries {
+        ProbeSeries{times: VecDeque::new(), values: Vec::new()}
+    }
+
+    /// Drops everything recorded and starts empty series for the given probes
+    pub fn clear(&mut self, probes: &[Probe]) {
+        self.times.clear();
+        self.values = vec![VecDeque::new(); probes.len()];
+    }
+
+    /// Appends the values of the field at the probes, dropping the oldest once MAX_SAMPLES are kept
+    pub fn record(&mut self, time: f64, probes: &[Probe], field: &HexField) {
+        self.times.push_back(time);
+        for (series, probe) in self.values.iter_mut().zip(probes.iter()) {
+            series.push_back(field[probe.i_y][probe.i_x]);
+        }
+        if self.times.len() > MAX_SAMPLES {
+            self.times.pop_front();
+            for series in self.values.iter_mut() {
+                series.pop_front();
+            }
+        }
+    }
+
+    /// Returns the spectrum of the last samples of a probe, at most max_samples
+    ///
+    /// The probes record every time step, the sample spacing is taken from the
+    /// recorded times.
+    pub fn spectrum(&self, probe: usize, window: Window, max_samples: usize) -> Spectrum {
+        let values: &VecDeque<f64> = &self.values[probe];
+        let start: usize = values.len().saturating_sub(max_samples);
+        let samples: usize = values.len() - start;
+        let time_spacing: f64 = if samples > 1 {
+            (self.times[self.times.len() - 1] - self.times[start])/(samples - 1) as f64
+        } else {
+            1.0
+        };
+        let last: Vec<f64> = values.range(start..).copied().collect();
+        spectrum::spectrum(&last, time_spacing, window)
+    }
+
+    /// Writes the series as CSV with a time column and one column per probe,
+    /// the probes are named by their coordinates
+    pub fn write_csv(&self, path: &str, coordinates: &[(f64, f64)]) -> Result<(), String> {
+        let error = |error: std::io::Error| format!("Could not write {}: {}", path, error);
+        let mut writer = BufWriter::new(File::create(path).map_err(error)?);
+        let mut header: String = String::from("time");
+        for (x, y) in coordinates.iter() {
+            header.push_str(&format!(",\"u({}, {})\"", x, y));
+        }
+        writeln!(writer, "{}", header).map_err(error)?;
+        for (i, time) in self.times.iter().enumerate() {
+            let mut line: String = time.to_string();
+            for series in self.values.iter() {
+                line.push_str(&format!(",{}", series[i]));
+            }
+            writeln!(writer, "{}", line).map_err(error)?;
+        }
+        writer.flush().map_err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_last_samples() {
+        let probes: Vec<Probe> = vec![Probe{i_x: 1, i_y: 1}];
+        let mut field: HexField = HexField::new(3, 3, 0.0);
+        let mut series: ProbeSeries = ProbeSeries::new();
+        series.clear(&probes);
+        for n in 0..MAX_SAMPLES + 10 {
+            field[1][1] = n as f64;
+            series.record(n as f64*0.1, &probes, &field);
+        }
+        assert_eq!(series.times.len(), MAX_SAMPLES);
+        assert_eq!(series.values[0].len(), MAX_SAMPLES);
+        assert_eq!(series.values[0][0], 10.0);
+        assert_eq!(series.times[0], 1.0);
+        assert_eq!(*series.values[0].back().unwrap(), (MAX_SAMPLES + 9) as f64);
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::io::BufWriter;
+use crate::export;
+use crate::functions;
+use crate::simulation::SimulationData;
+
+/// Delay between the frames of a GIF in hundredths of a second, 25 frames per second
+const GIF_FRAME_DELAY: u16 = 4;
+/// Effort of the colour quantisation of GIF frames, 1 is best and 30 fastest
+const GIF_SPEED: i32 = 10;
+
+/// Records the frames of a run to an animated GIF or a numbered PNG sequence
+///
+/// Paths ending in .gif give an animation, any other path is numbered, so
+/// "run.png" gives "run_00000.png", "run_00001.png" and so on, ready for ffmpeg.
+/// The colour scale is fixed by the first frame that is not at rest, so all
+/// frames of a recording are comparable.
+pub struct Recorder {
+    path: String,
+    /// Only every nth offered frame is written
+    every: u32,
+    /// Pixels across a hexagon, 1 writes one pixel per cell
+    scale: usize,
+    /// Interpolates between the cell centres, see export::frame_image
+    smooth: bool,
+    /// Field value mapped to the ends of the colour map, 0 until it is fixed
+    max_val: f64,
+    /// Frames offered so far, written or not
+    offered: u32,
+    /// Frames written so far
+    pub frames: u32,
+    gif: Option<gif::Encoder<BufWriter<File>>>,
+}
+
+impl Recorder {
+    pub fn new(path: &str, every: u32, scale: usize, smooth: bool) -> Recorder {
+        Recorder {
+            path: String::from(path),
+            every: every.max(1),
+            scale: scale.max(1),
+            smooth,
+            max_val: 0.0,
+            offered: 0,
+            frames: 0,
+            gif: None,
+        }
+    }
+
+    /// Offers the current frame of a simulation, called once per time step
+    pub fn record(&mut self, sim: &SimulationData) -> Result<(), String> {
+        self.offered += 1;
+        if !(self.offered - 1).is_multiple_of(self.every) {
+            return Ok(())
+        }
+        if self.max_val == 0.0 {
+            self.max_val = functions::get_max_abs(sim.levels.lock().current());
+        }
+        // A field at rest looks the same at every scale
+        let max_val: f64 = if self.max_val > 0.0 {self.max_val} else {1.0};
+        let (image_vec, width, height) = export::frame_image(sim, max_val, self.scale, self.smooth);
+        if self.path.to_lowercase().ends_with(".gif") {
+            self.write_gif_frame(&image_vec, width, height)?;
+        } else {
+            let stem: &str = self.path.strip_suffix(".png").unwrap_or(&self.path);
+            export::save_rgb(&format!("{}_{:05}.png", stem, self.frames), &image_vec, width, height)?;
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Appends a frame to the GIF, which is created with the size of the first frame
+    fn write_gif_frame(&mut self, image_vec: &[u8], width: usize, height: usize) -> Result<(), String> {
+        if width > u16::MAX as usize || height > u16::MAX as usize {
+            return Err(format!("Frames of {}x{} pixels are too large for a GIF", width, height))
+        }
+        let path: &str = &self.path;
+        if self.gif.is_none() {
+            let file: File = File::create(path).map_err(|error| format!("Could not write {}: {}", path, error))?;
+            let mut encoder = gif::Encoder::new(BufWriter::new(file), width as u16, height as u16, &[]).map_err(|error| format!("Could not write {}: {}", path, error))?;
+            encoder.set_repeat(gif::Repeat::Infinite).map_err(|error| format!("Could not write {}: {}", path, error))?;
+            self.gif = Some(encoder);
+        }
+        let mut frame = gif::Frame::from_rgb_speed(width as u16, height as u16, image_vec, GIF_SPEED);
+        frame.delay = GIF_FRAME_DELAY;
+        self.gif.as_mut().unwrap().write_frame(&frame).map_err(|error| format!("Could not write {}: {}", path, error))
+    }
+
+    /// Closes the recording and returns the number of frames written
+    pub fn finish(self) -> u32 {
+        // The encoder writes the end of the GIF when it is dropped
+        self.frames
+    }
+}
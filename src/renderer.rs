@@ -0,0 +1,148 @@
+use std::sync::Arc;
+use druid::kurbo::{Circle, Point, Rect};
+use druid::piet::{Image, ImageFormat, InterpolationMode};
+use druid::{Color, Data, PaintCtx, RenderContext, Size};
+use crate::cell::CellMask;
+use crate::field::{HexField, SharedLevels};
+use crate::functions::{self, Shading};
+use crate::modes::Modes;
+use crate::simulation::SimulationData;
+
+/// Colours of the probes on the grid and in the plot, repeated for more probes
+pub const PROBE_COLOURS: [Color; 6] = [Color::rgb8(255, 255, 255), Color::rgb8(240, 80, 80), Color::rgb8(90, 220, 90),
+    Color::rgb8(250, 200, 60), Color::rgb8(200, 110, 240), Color::rgb8(70, 210, 230)];
+/// Colour of the wall cells when they are shown
+const WALL_COLOUR: [u8; 3] = [150, 150, 150];
+const MARK_COLOUR: Color = Color::rgb8(230, 230, 230);
+
+/// Field shown by a view of the grid
+#[derive(Clone)]
+pub enum Layer {
+    /// The wave at the current time level, which changes with every time step and every edit
+    Wave(SharedLevels, u32, u32),
+    /// A map of the medium, replaced rather than changed in place
+    Map(Arc<HexField>),
+    /// An eigenmode by its index
+    Mode(Arc<Modes>, usize),
+}
+
+impl Layer {
+    /// Returns the current time level of a simulation, told apart by its step and edit counters
+    pub fn wave(sim: &SimulationData) -> Layer {
+        Layer::Wave(sim.levels.clone(), sim.n, sim.revision)
+    }
+}
+
+impl Data for Layer {
+    fn same(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Layer::Wave(levels, n, revision), Layer::Wave(other_levels, other_n, other_revision)) =>
+                levels.same(other_levels) && n == other_n && revision == other_revision,
+            (Layer::Map(field), Layer::Map(other_field)) => field.same(other_field),
+            (Layer::Mode(modes, k), Layer::Mode(other_modes, other_k)) => modes.same(other_modes) && k == other_k,
+            _ => false,
+        }
+    }
+}
+
+/// What is drawn over the field
+pub struct Overlays {
+    /// Colours the wall cells around the shape, part of the cached picture
+    pub walls: bool,
+    pub sources: bool,
+    pub probes: bool,
+    /// Centre and radius of the brush under the mouse
+    pub cursor: Option<(Point, f64)>,
+}
+
+/// Everything the cached picture depends on
+#[derive(Clone, Data)]
+struct Drawn {
+    layer: Layer,
+    hex_grid: Arc<CellMask>,
+    cmap: Arc<[[u8; 3]; 256]>,
+    width: usize,
+    height: usize,
+    smooth: bool,
+    walls: bool,
+}
+
+/// Draws a field on the grid of a simulation as hexagons, with overlays on top
+///
+/// The picture of the field is kept between paints and only computed again
+/// when the field, the grid, the size of the view or the shading change, so
+/// moving the mouse or repainting a paused run costs no more than drawing the
+/// image and the overlays.
+pub struct FieldRenderer {
+    image: Option<Image>,
+    drawn: Option<Drawn>,
+}
+
+impl FieldRenderer {
+    pub fn new() -> FieldRenderer {
+        FieldRenderer {
+            image: None,
+            drawn: None,
+        }
+    }
+
+    /// Draws a layer of a simulation filling a view of the given size, one image pixel per pixel of the view
+    pub fn paint(&mut self, ctx: &mut PaintCtx, sim: &SimulationData, layer: Layer, size: Size, smooth: bool, overlays: &Overlays) {
+        let drawn: Drawn = Drawn {
+            layer,
+            hex_grid: sim.hex_grid.clone(),
+            cmap: sim.cmap.clone(),
+            width: size.width.round() as usize,
+            height: size.height.round() as usize,
+            smooth,
+            walls: overlays.walls,
+        };
+        // A view without area has nothing to show, and no image of that size can be made
+        if drawn.width == 0 || drawn.height == 0 {
+            return;
+        }
+        // A picture that could not be made is not tried again until something changes
+        if !self.drawn.as_ref().is_some_and(|cached| cached.same(&drawn)) {
+            let image_vec: Vec<u8> = match &drawn.layer {
+                Layer::Wave(levels, _n, _revision) => picture(levels.lock().current(), &drawn),
+                Layer::Map(field) => picture(field, &drawn),
+                Layer::Mode(modes, k) => picture(&modes.shapes[*k], &drawn),
+            };
+            self.image = match ctx.make_image(drawn.width, drawn.height, &image_vec, ImageFormat::Rgb) {
+                Ok(image) => Some(image),
+                Err(error) => {
+                    println!("Could not create the picture: {}", error);
+                    None
+                }
+            };
+            self.drawn = Some(drawn);
+        }
+        if let Some(image) = &self.image {
+            ctx.draw_image(image, Rect{x0: 0.0, y0: 0.0, x1: size.width, y1: size.height}, InterpolationMode::NearestNeighbor);
+        }
+        let (x_len, y_len) = (sim.hex_grid.x_len(), sim.hex_grid.y_len());
+        if overlays.sources {
+            for source in sim.sources.iter() {
+                let (x, y) = functions::picture_position(source.i_x, source.i_y, x_len, y_len);
+                ctx.stroke(Circle{center: Point{x: x*size.width, y: y*size.height}, radius: 4.0}, &MARK_COLOUR, 1.5);
+            }
+        }
+        if overlays.probes {
+            for (k, probe) in sim.probes.iter().enumerate() {
+                let (x, y) = functions::picture_position(probe.i_x, probe.i_y, x_len, y_len);
+                let (x, y) = (x*size.width, y*size.height);
+                ctx.stroke(Rect{x0: x - 3.0, y0: y - 3.0, x1: x + 3.0, y1: y + 3.0}, &PROBE_COLOURS[k%PROBE_COLOURS.len()], 1.5);
+            }
+        }
+        if let Some((center, radius)) = overlays.cursor {
+            ctx.fill(Circle{center, radius}.segment(radius - 1.0, 0.0, 6.3), &MARK_COLOUR);
+        }
+    }
+}
+
+/// Returns the picture of a field scaled to its largest magnitude, see functions::picture
+fn picture(field: &HexField, drawn: &Drawn) -> Vec<u8> {
+    let wall_colour: Option<[u8; 3]> = if drawn.walls {Some(WALL_COLOUR)} else {None};
+    let shading: Shading = Shading{max_val: functions::get_max_abs(field), smooth: drawn.smooth, wall_colour};
+    functions::picture(field, &drawn.hex_grid, &drawn.cmap, drawn.width, drawn.height, &shading)
+}
@@ -0,0 +1,692 @@
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use crate::boundary::{self, BoundaryCondition};
+use crate::functions;
+use crate::medium::PaintedCell;
+use crate::params::{SimulationParams, MAX_THREADS};
+use crate::shape::Shape;
+use crate::simulation::SimulationData;
+use crate::source::{self, Source, SourceKind};
+
+/// Value of a key in a scenario file
+#[derive(Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+}
+
+/// A table of a scenario file, the keys before the first header belong to the
+/// table with the empty name
+pub struct Table {
+    pub name: String,
+    /// Number of the line of the header, 0 for the keys before the first header
+    pub line: usize,
+    /// Keys with their values and the number of the line they are set in
+    pub entries: Vec<(String, Value, usize)>,
+}
+
+impl Table {
+    fn get(&self, key: &str) -> Option<(&Value, usize)> {
+        self.entries.iter().find(|(name, _value, _line)| name == key).map(|(_name, value, line)| (value, *line))
+    }
+
+    /// Returns the number of a key or the default if the key is missing
+    pub fn number(&self, key: &str, default: f64) -> Result<f64, String> {
+        match self.get(key) {
+            Some((Value::Number(number), _line)) => Ok(*number),
+            Some((_, line)) => Err(format!("line {}: {} has to be a number", line, key)),
+            None => Ok(default),
+        }
+    }
+
+    /// Returns the whole number of a key from 0 to max, or the default if the key is missing
+    pub fn count(&self, key: &str, default: usize, max: usize) -> Result<usize, String> {
+        let number: f64 = self.number(key, default as f64)?;
+        match self.get(key) {
+            Some((_value, line)) if number < 0.0 || number.fract() != 0.0 || number > max as f64 =>
+                Err(format!("line {}: {} has to be a whole number from 0 to {}", line, key, max)),
+            _ => Ok(number as usize),
+        }
+    }
+
+    /// Returns the string of a key if there is one
+    pub fn text(&self, key: &str) -> Result<Option<String>, String> {
+        match self.get(key) {
+            Some((Value::Text(text), _line)) => Ok(Some(text.clone())),
+            Some((_, line)) => Err(format!("line {}: {} has to be a string", line, key)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the boolean of a key or the default if the key is missing
+    pub fn boolean(&self, key: &str, default: bool) -> Result<bool, String> {
+        match self.get(key) {
+            Some((Value::Bool(boolean), _line)) => Ok(*boolean),
+            Some((_, line)) => Err(format!("line {}: {} has to be true or false", line, key)),
+            None => Ok(default),
+        }
+    }
+
+    /// Returns the path of a file that has to exist relative to the working directory, see resolve
+    fn file(&self, key: &str, dir: &Path) -> Result<Option<String>, String> {
+        match (self.text(key)?, self.get(key)) {
+            (Some(file), Some((_value, line))) => {
+                let file: String = resolve(dir, file);
+                if !Path::new(&file).is_file() {
+                    return Err(format!("line {}: {} {} does not exist", line, key, file))
+                }
+                Ok(Some(file))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Refuses keys other than the given ones, so a misspelled key does not
+    /// silently leave its value at the default
+    fn only(&self, keys: &[&str]) -> Result<(), String> {
+        match self.entries.iter().find(|(name, _value, _line)| !keys.contains(&name.as_str())) {
+            Some((name, _value, line)) => Err(format!("line {}: unknown key {}", line, name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns a value parsed from the string of a key, or the default if the key is missing
+    fn parsed<T>(&self, key: &str, default: T, parse: fn(&str) -> Option<T>) -> Result<T, String> {
+        match (self.text(key)?, self.get(key)) {
+            (Some(name), Some((_value, line))) => parse(&name).ok_or_else(|| format!("line {}: unknown {} {}", line, key, name)),
+            _ => Ok(default),
+        }
+    }
+}
+
+/// Parses the subset of TOML used by scenario files
+///
+/// Supported are comments, [table] and [[table]] headers and "key = value"
+/// lines with numbers, booleans and double quoted strings. Every header starts
+/// a new table, so repeated [[table]] headers give one table each. A line that
+/// is none of these is an error naming its number.
+pub fn parse_tables(content: &str) -> Result<Vec<Table>, String> {
+    let mut tables: Vec<Table> = vec!(Table{name: String::new(), line: 0, entries: Vec::new()});
+    for (number, line) in content.lines().enumerate() {
+        let number: usize = number + 1;
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            let name: &str = line.trim_start_matches('[').split(']').next().unwrap_or("").trim();
+            tables.push(Table{name: name.to_string(), line: number, entries: Vec::new()});
+            continue;
+        }
+        let mut parts = line.splitn(2, '=');
+        let key: &str = parts.next().unwrap_or("").trim();
+        let raw: &str = match parts.next() {
+            Some(raw) if !key.is_empty() => raw.trim(),
+            _ => return Err(format!("line {}: expected key = value", number)),
+        };
+        let value: Value = if let Some(quoted) = raw.strip_prefix('"') {
+            // Reading up to the closing quote, backslashes escape the next character
+            let mut text: String = String::new();
+            let mut chars = quoted.chars();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => text.extend(chars.next()),
+                    Some(character) => text.push(character),
+                    None => return Err(format!("line {}: unterminated string", number)),
+                }
+            }
+            Value::Text(text)
+        } else {
+            let raw: &str = raw.split('#').next().unwrap_or("").trim();
+            match raw {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                _ => match f64::from_str(raw) {
+                    Ok(number) => Value::Number(number),
+                    Err(_) => return Err(format!("line {}: could not parse the value {}", number, raw)),
+                },
+            }
+        };
+        tables.last_mut().unwrap().entries.push((key.to_string(), value, number));
+    }
+    Ok(tables)
+}
+
+/// Reads the cells of a map painted by hand, one "x y value" line per cell
+fn read_cells(path: &str) -> Result<Vec<PaintedCell>, String> {
+    let content: String = std::fs::read_to_string(path).map_err(|error| format!("could not read {}: {}", path, error))?;
+    let mut cells: Vec<PaintedCell> = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let numbers: Vec<f64> = line.split_whitespace().map(f64::from_str).collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| format!("{} line {}: could not parse {}", path, number + 1, line.trim()))?;
+        match numbers[..] {
+            [x, y, value] => cells.push((x, y, value)),
+            _ => return Err(format!("{} line {}: expected x y value", path, number + 1)),
+        }
+    }
+    Ok(cells)
+}
+
+/// Writes cells in the format read by read_cells
+fn write_cells(path: &str, cells: &[PaintedCell]) -> Result<(), String> {
+    let content: String = cells.iter().map(|(x, y, value)| format!("{} {} {}\n", x, y, value)).collect();
+    std::fs::write(path, content).map_err(|error| format!("Could not write {}: {}", path, error))
+}
+
+/// A displacement set up before the simulation starts
+#[derive(Clone, Copy, PartialEq)]
+pub enum Pulse {
+    /// Displacement of the cell closest to x, y
+    Point {x: f64, y: f64, height: f64},
+    /// Gaussian around x, y with the standard deviation width
+    Gauss {x: f64, y: f64, width: f64, height: f64},
+}
+
+/// Everything needed to set up and run a simulation
+pub struct Scenario {
+    /// Shape file, relative paths are resolved from the directory of the scenario
+    pub shape: String,
+    pub speed_map: Option<String>,
+    pub damping_map: Option<String>,
+    /// Cells of the speed map painted by hand with their coordinates, set on top of the speed map
+    pub speed_cells: Vec<PaintedCell>,
+    /// Cells of the damping map painted by hand, like speed_cells
+    pub damping_cells: Vec<PaintedCell>,
+    pub params: SimulationParams,
+    pub boundary: BoundaryCondition,
+    /// Global damping coefficient
+    pub damping: f64,
+    /// Number of time steps of a headless run
+    pub steps: u32,
+    pub pulses: Vec<Pulse>,
+    /// Sources together with the coordinates of their cell
+    pub sources: Vec<(f64, f64, Source)>,
+    /// Coordinates of the probes
+    pub probes: Vec<(f64, f64)>,
+    /// Lines of the tables of the pulses, sources and probes in the file they
+    /// were loaded from, empty for a captured scenario
+    pub pulse_lines: Vec<usize>,
+    pub source_lines: Vec<usize>,
+    pub probe_lines: Vec<usize>,
+    /// Colormap file, the default map is used without one
+    pub colormap: Option<String>,
+    /// Time between two frames of the application in milliseconds
+    pub frame_interval: u64,
+    /// Time steps the application computes between two frames
+    pub steps_per_frame: u32,
+    pub output: Output,
+}
+
+/// Files a headless run writes when it is done
+#[derive(Clone, Default)]
+pub struct Output {
+    /// Image of the last frame
+    pub png: Option<String>,
+    /// Last field as CSV
+    pub csv: Option<String>,
+    /// Values recorded by the probes as CSV
+    pub probes: Option<String>,
+    /// Energy and norms of every time step as CSV
+    pub energy: Option<String>,
+}
+
+/// Returns a path of a scenario file relative to the working directory
+fn resolve(dir: &Path, path: String) -> String {
+    if Path::new(&path).is_absolute() {
+        return path
+    }
+    dir.join(path).to_string_lossy().into_owned()
+}
+
+/// Returns a path relative to the directory a scenario is saved in, so the
+/// scenario can be moved together with its files
+fn relative(dir: &Path, path: &str) -> String {
+    let (dir, target) = match (dir.canonicalize(), Path::new(path).canonicalize()) {
+        (Ok(dir), Ok(target)) => (dir, target),
+        _ => return String::from(path),
+    };
+    let shared: Vec<Component> = dir.components().zip(target.components()).take_while(|(a, b)| a == b).map(|(a, _b)| a).collect();
+    // Paths sharing nothing but the root stay absolute
+    if !shared.iter().any(|component| matches!(component, Component::Normal(_))) {
+        return target.to_string_lossy().into_owned()
+    }
+    let common: usize = shared.len();
+    let mut relative: PathBuf = PathBuf::new();
+    for _ in dir.components().skip(common) {
+        relative.push("..");
+    }
+    for component in target.components().skip(common) {
+        relative.push(component);
+    }
+    relative.to_string_lossy().into_owned()
+}
+
+/// Returns a string in quotes with quotes and backslashes escaped
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl Scenario {
+    /// Loads a scenario file, see the README for the format
+    ///
+    /// A file that cannot be read or parsed, or that names files which do not
+    /// exist, is an error with the number of the offending line.
+    pub fn load(path: &str) -> Result<Scenario, String> {
+        let content: String = std::fs::read_to_string(path).map_err(|error| format!("Could not read scenario {}: {}", path, error))?;
+        let dir: &Path = Path::new(path).parent().unwrap_or(Path::new(""));
+        Scenario::parse(&content, dir).map_err(|error| format!("Could not load scenario {}: {}", path, error))
+    }
+
+    /// Parses the content of a scenario file, relative paths are resolved from dir
+    fn parse(content: &str, dir: &Path) -> Result<Scenario, String> {
+        let tables: Vec<Table> = parse_tables(content)?;
+        let root: &Table = &tables[0];
+        root.only(&["shape", "speed_map", "damping_map", "speed_cells", "damping_cells", "colormap", "boundary", "damping",
+            "steps", "frame_interval", "steps_per_frame"])?;
+        let mut scenario = Scenario {
+            shape: root.file("shape", dir)?.ok_or_else(|| String::from("the scenario needs a shape file"))?,
+            speed_map: root.file("speed_map", dir)?,
+            damping_map: root.file("damping_map", dir)?,
+            speed_cells: match root.file("speed_cells", dir)? {
+                Some(file) => read_cells(&file)?,
+                None => Vec::new(),
+            },
+            damping_cells: match root.file("damping_cells", dir)? {
+                Some(file) => read_cells(&file)?,
+                None => Vec::new(),
+            },
+            params: SimulationParams::default(),
+            boundary: root.parsed("boundary", BoundaryCondition::Reflecting, boundary::parse_boundary_condition)?,
+            damping: root.number("damping", 0.0)?,
+            steps: root.number("steps", 3000.0)? as u32,
+            pulses: Vec::new(),
+            sources: Vec::new(),
+            probes: Vec::new(),
+            pulse_lines: Vec::new(),
+            source_lines: Vec::new(),
+            probe_lines: Vec::new(),
+            colormap: root.file("colormap", dir)?,
+            frame_interval: root.number("frame_interval", 50.0)? as u64,
+            steps_per_frame: root.number("steps_per_frame", 1.0)?.max(1.0) as u32,
+            output: Output::default(),
+        };
+        for table in tables.iter().skip(1) {
+            match table.name.as_str() {
+                "params" => {
+                    table.only(&["speed", "spacing", "time_spacing", "threads"])?;
+                    let default: SimulationParams = SimulationParams::default();
+                    scenario.params = SimulationParams {
+                        speed: table.number("speed", default.speed)?,
+                        spacing: table.number("spacing", default.spacing)?,
+                        time_spacing: table.number("time_spacing", default.time_spacing)?,
+                        threads: table.count("threads", default.threads, MAX_THREADS)?,
+                    };
+                }
+                "point" => {
+                    table.only(&["x", "y", "height"])?;
+                    scenario.pulses.push(Pulse::Point {
+                        x: table.number("x", 0.0)?,
+                        y: table.number("y", 0.0)?,
+                        height: table.number("height", 1.0)?,
+                    });
+                    scenario.pulse_lines.push(table.line);
+                }
+                "gauss" => {
+                    table.only(&["x", "y", "width", "height"])?;
+                    scenario.pulses.push(Pulse::Gauss {
+                        x: table.number("x", 0.0)?,
+                        y: table.number("y", 0.0)?,
+                        width: table.number("width", 0.1)?,
+                        height: table.number("height", 1.0)?,
+                    });
+                    scenario.pulse_lines.push(table.line);
+                }
+                "source" => {
+                    table.only(&["kind", "x", "y", "active", "amplitude", "frequency", "phase", "sweep", "duty"])?;
+                    let kind = table.parsed("kind", SourceKind::Sine, source::parse_source_kind)?;
+                    let line: usize = table.get("frequency").map_or(table.line, |(_value, line)| line);
+                    let mut source: Source = Source::new(kind, 0, 0, table.number("amplitude", 1.0)?, table.number("frequency", 1.0)?)
+                        .map_err(|error| format!("line {}: {}", line, error))?;
+                    source.active = table.boolean("active", true)?;
+                    source.phase = table.number("phase", source.phase)?;
+                    source.sweep = table.number("sweep", source.sweep)?;
+                    source.duty = table.number("duty", source.duty)?;
+                    scenario.sources.push((table.number("x", 0.0)?, table.number("y", 0.0)?, source));
+                    scenario.source_lines.push(table.line);
+                }
+                "probe" => {
+                    table.only(&["x", "y"])?;
+                    scenario.probes.push((table.number("x", 0.0)?, table.number("y", 0.0)?));
+                    scenario.probe_lines.push(table.line);
+                }
+                "output" => {
+                    table.only(&["png", "csv", "probes", "energy"])?;
+                    scenario.output = Output {
+                        png: table.text("png")?.map(|file| resolve(dir, file)),
+                        csv: table.text("csv")?.map(|file| resolve(dir, file)),
+                        probes: table.text("probes")?.map(|file| resolve(dir, file)),
+                        energy: table.text("energy")?.map(|file| resolve(dir, file)),
+                    };
+                }
+                other => return Err(format!("line {}: unknown table [{}]", table.line, other)),
+            }
+        }
+        Ok(scenario)
+    }
+
+    /// Returns the scenario that sets up a simulation as it was before the
+    /// first time step of its run, with the pulses added by hand and the
+    /// painted cells of its maps
+    ///
+    /// Pulses added after the run started are left out, a scenario starts
+    /// from rest and cannot place them at the time step they were added.
+    pub fn capture(sim: &SimulationData, colormap: Option<String>, frame_interval: u64, steps_per_frame: u32) -> Scenario {
+        let sources: Vec<(f64, f64, Source)> = sim.sources.iter().map(|source| {
+            let (x, y) = functions::get_cord(source.i_x, source.i_y, sim.x_off, sim.y_off, sim.params.spacing);
+            (x, y, source.clone())
+        }).collect();
+        let (start, pulses): (u32, &Vec<Pulse>) = match &sim.initial {
+            Some(initial) => (initial.n, &initial.pulses),
+            None => (sim.n, &sim.pulses),
+        };
+        if start > 0 {
+            println!("The run started at time step {}, the scenario starts from rest with the pulses added by hand", start);
+        }
+        if pulses.len() < sim.pulses.len() {
+            println!("Leaving out {} pulses added during the run", sim.pulses.len() - pulses.len());
+        }
+        let (speed_cells, damping_cells) = sim.painted_cells();
+        Scenario {
+            shape: sim.shape.path.clone(),
+            speed_map: sim.shape.speed_path.clone(),
+            damping_map: sim.shape.damping_path.clone(),
+            speed_cells,
+            damping_cells,
+            params: sim.params,
+            boundary: sim.boundary,
+            damping: sim.damping,
+            steps: sim.n_max,
+            pulses: pulses.to_vec(),
+            sources,
+            probes: sim.probe_coordinates(),
+            pulse_lines: Vec::new(),
+            source_lines: Vec::new(),
+            probe_lines: Vec::new(),
+            colormap,
+            frame_interval,
+            steps_per_frame,
+            output: Output::default(),
+        }
+    }
+
+    /// Writes the scenario in the format read by load, paths are stored
+    /// relative to the scenario where possible
+    ///
+    /// Painted cells go to files next to the scenario, named after it with
+    /// _speed_cells.txt and _damping_cells.txt appended.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let dir: &Path = match Path::new(path).parent() {
+            Some(dir) if dir != Path::new("") => dir,
+            _ => Path::new("."),
+        };
+        let stem: String = Path::new(path).file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut content: String = String::new();
+        content.push_str(&format!("shape = {}\n", quote(&relative(dir, &self.shape))));
+        for (key, value) in [("speed_map", &self.speed_map), ("damping_map", &self.damping_map), ("colormap", &self.colormap)].iter() {
+            if let Some(file) = value {
+                content.push_str(&format!("{} = {}\n", key, quote(&relative(dir, file))));
+            }
+        }
+        for (key, cells) in [("speed_cells", &self.speed_cells), ("damping_cells", &self.damping_cells)].iter() {
+            if !cells.is_empty() {
+                let file: String = dir.join(format!("{}_{}.txt", stem, key)).to_string_lossy().into_owned();
+                write_cells(&file, cells)?;
+                content.push_str(&format!("{} = {}\n", key, quote(&relative(dir, &file))));
+            }
+        }
+        content.push_str(&format!("boundary = {}\n", quote(&self.boundary.name().to_lowercase())));
+        content.push_str(&format!("damping = {}\n", self.damping));
+        content.push_str(&format!("steps = {}\n", self.steps));
+        content.push_str(&format!("frame_interval = {}\n", self.frame_interval));
+        content.push_str(&format!("steps_per_frame = {}\n", self.steps_per_frame));
+        content.push_str(&format!("\n[params]\nspeed = {}\nspacing = {}\ntime_spacing = {}\nthreads = {}\n",
+            self.params.speed, self.params.spacing, self.params.time_spacing, self.params.threads));
+        for pulse in self.pulses.iter() {
+            match *pulse {
+                Pulse::Point {x, y, height} => content.push_str(&format!("\n[[point]]\nx = {}\ny = {}\nheight = {}\n", x, y, height)),
+                Pulse::Gauss {x, y, width, height} => content.push_str(&format!("\n[[gauss]]\nx = {}\ny = {}\nwidth = {}\nheight = {}\n", x, y, width, height)),
+            }
+        }
+        for (x, y, source) in self.sources.iter() {
+            content.push_str(&format!("\n[[source]]\nkind = {}\nx = {}\ny = {}\nactive = {}\namplitude = {}\nfrequency = {}\nphase = {}\nsweep = {}\nduty = {}\n",
+                quote(&source.kind.name().to_lowercase()), x, y, source.active, source.amplitude, source.frequency, source.phase, source.sweep, source.duty));
+        }
+        for (x, y) in self.probes.iter() {
+            content.push_str(&format!("\n[[probe]]\nx = {}\ny = {}\n", x, y));
+        }
+        let outputs: [(&str, &Option<String>); 4] = [("png", &self.output.png), ("csv", &self.output.csv),
+            ("probes", &self.output.probes), ("energy", &self.output.energy)];
+        if outputs.iter().any(|(_key, value)| value.is_some()) {
+            content.push_str("\n[output]\n");
+            for (key, value) in outputs.iter() {
+                if let Some(file) = value {
+                    content.push_str(&format!("{} = {}\n", key, quote(&relative(dir, file))));
+                }
+            }
+        }
+        std::fs::write(path, content).map_err(|error| format!("Could not write scenario {}: {}", path, error))
+    }
+
+    /// Builds the simulation of the scenario with the pulses and sources in place
+    ///
+    /// A shape file that cannot be loaded, or a pulse, source or probe outside
+    /// of the shape, is an error naming the line of its table.
+    pub fn build(&self, cmap: Arc<[[u8; 3]; 256]>) -> Result<SimulationData, String> {
+        let mut shape: Shape = Shape::load(&self.shape)?;
+        shape.speed_path = self.speed_map.clone();
+        shape.damping_path = self.damping_map.clone();
+        let mut sim: SimulationData = SimulationData::new(Arc::new(shape), self.params, cmap);
+        sim.boundary = self.boundary;
+        sim.damping = self.damping;
+        sim.n_max = self.steps;
+        // Painted cells are set on top of the maps, the time step is shortened again for faster cells
+        for &(x, y, speed) in self.speed_cells.iter() {
+            match sim.cell_at(x, y) {
+                Some((i_x, i_y)) => Arc::make_mut(&mut sim.hex_speed)[i_y][i_x] = speed,
+                None => println!("Painted cell at {} {} lies outside of the grid", x, y),
+            }
+        }
+        for &(x, y, damping) in self.damping_cells.iter() {
+            match sim.cell_at(x, y) {
+                Some((i_x, i_y)) => Arc::make_mut(&mut sim.hex_damp)[i_y][i_x] = damping,
+                None => println!("Painted cell at {} {} lies outside of the grid", x, y),
+            }
+        }
+        sim.update_max_speed();
+        sim.set_time_spacing(sim.params.time_spacing);
+        for (k, pulse) in self.pulses.iter().enumerate() {
+            let (x, y) = match *pulse {
+                Pulse::Point {x, y, ..} | Pulse::Gauss {x, y, ..} => (x, y),
+            };
+            match (sim.cell_at(x, y), *pulse) {
+                (Some((i_x, i_y)), Pulse::Point {height, ..}) => sim.add_point(i_x, i_y, height),
+                (Some((i_x, i_y)), Pulse::Gauss {width, height, ..}) => sim.add_gauss(i_x, i_y, width, height),
+                (None, _) => return Err(outside(&self.pulse_lines, k, "pulse", x, y, "grid")),
+            }
+        }
+        for (k, (x, y, source)) in self.sources.iter().enumerate() {
+            match sim.cell_at(*x, *y) {
+                Some((i_x, i_y)) if sim.hex_grid[i_y][i_x].is_active() => {
+                    let mut placed: Source = source.clone();
+                    placed.i_x = i_x;
+                    placed.i_y = i_y;
+                    sim.place_source(placed);
+                }
+                _ => return Err(outside(&self.source_lines, k, "source", *x, *y, "shape")),
+            }
+        }
+        for (k, (x, y)) in self.probes.iter().enumerate() {
+            match sim.cell_at(*x, *y) {
+                Some((i_x, i_y)) if sim.hex_grid[i_y][i_x].is_active() => sim.place_probe(i_x, i_y),
+                _ => return Err(outside(&self.probe_lines, k, "probe", *x, *y, "shape")),
+            }
+        }
+        Ok(sim)
+    }
+}
+
+/// Returns the error of the kth pulse, source or probe lying outside of the grid or the shape, with its line if it has one
+fn outside(lines: &[usize], k: usize, what: &str, x: f64, y: f64, region: &str) -> String {
+    match lines.get(k) {
+        Some(line) => format!("line {}: {} at {} {} lies outside of the {}", line, what, x, y, region),
+        None => format!("{} at {} {} lies outside of the {}", what, x, y, region),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::boundary::EdgeType;
+    use crate::field::HexField;
+
+    /// Returns an empty directory for the files of a test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir: PathBuf = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Returns the path of a shape file with the square of Shape::square, written to dir
+    fn square(dir: &Path) -> String {
+        let path: String = dir.join("square.txt").to_string_lossy().into_owned();
+        let shape: Shape = Shape::square(EdgeType::Default);
+        let lines: Vec<String> = (0..shape.x1.len()).map(|k| format!("{} {} {} {}\n", shape.x1[k], shape.y1[k], shape.x2[k], shape.y2[k])).collect();
+        std::fs::write(&path, lines.concat()).unwrap();
+        path
+    }
+
+    fn error(result: Result<Scenario, String>) -> String {
+        match result {
+            Ok(_) => panic!("Parsed an invalid scenario"),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn parses_the_toml_subset() {
+        let tables: Vec<Table> = parse_tables("# comment\nname = \"a \\\"b\\\" \\\\ c\" # note\n\n[params]\nspeed = 0.5 # slow\n\n[[probe]]\nx = -1e-2\n[[probe]]\nactive = false\n").unwrap();
+        assert_eq!(tables.len(), 4);
+        assert!(tables[0].text("name").unwrap() == Some(String::from("a \"b\" \\ c")));
+        assert_eq!(tables[1].name, "params");
+        assert_eq!(tables[1].number("speed", 1.0).unwrap(), 0.5);
+        assert_eq!(tables[2].number("x", 0.0).unwrap(), -0.01);
+        assert!(!tables[3].boolean("active", true).unwrap());
+        assert_eq!(tables[3].number("x", 3.0).unwrap(), 3.0);
+        assert_eq!(tables[3].number("active", 1.0).err().unwrap(), "line 10: active has to be a number");
+    }
+
+    #[test]
+    fn reports_the_line_of_an_error() {
+        assert_eq!(parse_tables("a = 1\n\njust text\n").err().unwrap(), "line 3: expected key = value");
+        assert_eq!(parse_tables("a = \"open\n").err().unwrap(), "line 1: unterminated string");
+        assert_eq!(parse_tables("a = 1\nb = 1.2.3\n").err().unwrap(), "line 2: could not parse the value 1.2.3");
+        let dir: PathBuf = test_dir("wave_reports_the_line_of_an_error");
+        let shape: String = square(&dir);
+        assert_eq!(error(Scenario::parse("steps = 10\n", &dir)), "the scenario needs a shape file");
+        assert_eq!(error(Scenario::parse("shape = \"missing.txt\"\n", &dir)),
+            format!("line 1: shape {} does not exist", dir.join("missing.txt").to_string_lossy()));
+        let content: String = format!("shape = {}\n\nboundary = \"wobbly\"\n", quote(&shape));
+        assert_eq!(error(Scenario::parse(&content, &dir)), "line 3: unknown boundary wobbly");
+        let content: String = format!("shape = {}\n[[source]]\nkind = \"ricker\"\nx = true\n", quote(&shape));
+        assert_eq!(error(Scenario::parse(&content, &dir)), "line 4: x has to be a number");
+        let content: String = format!("shape = {}\nn_mx = 5000\n", quote(&shape));
+        assert_eq!(error(Scenario::parse(&content, &dir)), "line 2: unknown key n_mx");
+        let content: String = format!("shape = {}\n[[probe]]\nx = 0\nz = 1\n", quote(&shape));
+        assert_eq!(error(Scenario::parse(&content, &dir)), "line 4: unknown key z");
+        let content: String = format!("shape = {}\n[[source]]\nkind = \"ricker\"\nfrequency = 0\n", quote(&shape));
+        assert_eq!(error(Scenario::parse(&content, &dir)), "line 4: the frequency of a source has to be above 0, not 0");
+        let content: String = format!("shape = {}\n[params]\nthreads = 2.5\n", quote(&shape));
+        assert_eq!(error(Scenario::parse(&content, &dir)), format!("line 3: threads has to be a whole number from 0 to {}", MAX_THREADS));
+        let content: String = format!("shape = {}\n[params]\nthreads = -1\n", quote(&shape));
+        assert_eq!(error(Scenario::parse(&content, &dir)), format!("line 3: threads has to be a whole number from 0 to {}", MAX_THREADS));
+        let content: String = format!("shape = {}\n\n[[probes]]\nx = 0\n", quote(&shape));
+        assert_eq!(error(Scenario::parse(&content, &dir)), "line 3: unknown table [probes]");
+        // Placing outside of the shape is only found when building
+        let content: String = format!("shape = {}\n[[probe]]\nx = 0\n[[source]]\nx = 0.5\ny = 0\n", quote(&shape));
+        let cmap: Arc<[[u8; 3]; 256]> = Arc::new([[0; 3]; 256]);
+        match Scenario::parse(&content, &dir).unwrap().build(cmap) {
+            Ok(_) => panic!("Built a source outside of the shape"),
+            Err(error) => assert_eq!(error, "line 4: source at 0.5 0 lies outside of the shape"),
+        }
+    }
+
+    #[test]
+    fn saves_what_it_loads() {
+        let dir: PathBuf = test_dir("wave_saves_what_it_loads");
+        let shape: String = square(&dir);
+        let content: String = format!("shape = {}\nboundary = \"absorbing\"\ndamping = 0.25\nsteps = 120\nframe_interval = 20\nsteps_per_frame = 3\n\
+            [params]\nspeed = 0.7\nspacing = 0.02\ntime_spacing = 0.004\nthreads = 2\n\
+            [[point]]\nx = 0.1\ny = -0.05\nheight = 2\n[[gauss]]\nx = 0\ny = 0.1\nwidth = 0.03\nheight = -1.5\n\
+            [[source]]\nkind = \"chirp\"\nx = -0.1\ny = 0.1\nactive = false\namplitude = 0.5\nfrequency = 3\nphase = 1.25\nsweep = 2\nduty = 0.3\n\
+            [[probe]]\nx = 0.05\ny = 0.05\n[output]\npng = \"last.png\"\n", quote(&shape));
+        let mut scenario: Scenario = Scenario::parse(&content, &dir).unwrap();
+        scenario.speed_cells = vec![(0.01, 0.02, 0.5), (-0.03, 0.04, 0.25)];
+        scenario.damping_cells = vec![(0.1, -0.1, 3.0)];
+        let first: String = dir.join("first.toml").to_string_lossy().into_owned();
+        scenario.save(&first).unwrap();
+        let loaded: Scenario = Scenario::load(&first).unwrap();
+        assert!(loaded.params == SimulationParams{speed: 0.7, spacing: 0.02, time_spacing: 0.004, threads: 2});
+        assert!(loaded.boundary == BoundaryCondition::Absorbing);
+        assert_eq!((loaded.damping, loaded.steps, loaded.frame_interval, loaded.steps_per_frame), (0.25, 120, 20, 3));
+        assert!(loaded.pulses == scenario.pulses);
+        assert!(loaded.speed_cells == scenario.speed_cells && loaded.damping_cells == scenario.damping_cells);
+        assert!(loaded.probes == vec![(0.05, 0.05)]);
+        let (x, y, source) = &loaded.sources[0];
+        assert!((*x, *y, source.kind, source.active) == (-0.1, 0.1, SourceKind::Chirp, false));
+        assert_eq!((source.amplitude, source.frequency, source.phase, source.sweep, source.duty), (0.5, 3.0, 1.25, 2.0, 0.3));
+        assert!(loaded.output.png == Some(dir.join("last.png").to_string_lossy().into_owned()));
+        // Saving the loaded scenario again writes the same file
+        let second: String = dir.join("second.toml").to_string_lossy().into_owned();
+        loaded.save(&second).unwrap();
+        let renamed: String = std::fs::read_to_string(&second).unwrap().replace("second_", "first_");
+        assert_eq!(renamed, std::fs::read_to_string(&first).unwrap());
+    }
+
+    #[test]
+    fn captures_the_start_of_a_run() {
+        let dir: PathBuf = test_dir("wave_captures_the_start_of_a_run");
+        let content: String = format!("shape = {}\n[params]\nspeed = 1\nspacing = 0.01\ntime_spacing = 0.005\nthreads = 1\n", quote(&square(&dir)));
+        let cmap: Arc<[[u8; 3]; 256]> = Arc::new([[0; 3]; 256]);
+        let mut sim: SimulationData = Scenario::parse(&content, &dir).unwrap().build(cmap.clone()).unwrap();
+        let (x_centre, y_centre) = (sim.hex_grid.x_len()/2, sim.hex_grid.y_len()/2);
+        sim.add_gauss(x_centre, y_centre, 0.05, 1.0);
+        sim.paint_speed(0.3, 0.4, 0.05, 0.5);
+        sim.paint_damping(0.6, 0.6, 0.03, 2.0);
+        for _ in 0..10 {
+            sim.calc_next_frame();
+        }
+        let expected: HexField = sim.levels.lock().current().clone();
+        // A pulse of the running simulation is not part of its scenario
+        sim.add_gauss(x_centre + 5, y_centre, 0.02, 1.0);
+        sim.calc_next_frame();
+        let path: String = dir.join("captured.toml").to_string_lossy().into_owned();
+        let captured: Scenario = Scenario::capture(&sim, None, 50, 1);
+        assert_eq!(captured.pulses.len(), 1);
+        assert!(!captured.speed_cells.is_empty() && !captured.damping_cells.is_empty());
+        captured.save(&path).unwrap();
+        let mut rebuilt: SimulationData = Scenario::load(&path).unwrap().build(cmap).unwrap();
+        assert!(rebuilt.hex_speed.data() == sim.hex_speed.data() && rebuilt.hex_damp.data() == sim.hex_damp.data());
+        for _ in 0..10 {
+            rebuilt.calc_next_frame();
+        }
+        assert!(rebuilt.levels.lock().current().data() == expected.data());
+    }
+}
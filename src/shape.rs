@@ -0,0 +1,112 @@
+use std::str::FromStr;
+use crate::boundary::{self, EdgeType};
+use crate::functions;
+
+/// Outline of the simulated shape as read from a shape file
+#[derive(Clone)]
+pub struct Shape {
+    /// Shape file the edges were read from
+    pub path: String,
+    pub x1: Vec<f64>,
+    pub y1: Vec<f64>,
+    pub x2: Vec<f64>,
+    pub y2: Vec<f64>,
+    pub edge_types: Vec<EdgeType>,
+    /// Region file with the wave speed map
+    pub speed_path: Option<String>,
+    /// Region file with the damping map
+    pub damping_path: Option<String>,
+}
+
+impl Shape {
+    /// Loads the edges of a shape file, see the README for the format
+    ///
+    /// Errors name the file and the line, empty lines are skipped.
+    pub fn load(path: &str) -> Result<Shape, String> {
+        if let Err(error) = std::fs::metadata(path) {
+            return Err(format!("Could not load shape {}: {}", path, error))
+        }
+        let data_array: Vec<Vec<String>> = functions::csv_parse(path, ' ');
+        // Setting up float vectors to fill
+        let mut shape = Shape {
+            path: String::from(path),
+            x1: Vec::new(),
+            y1: Vec::new(),
+            x2: Vec::new(),
+            y2: Vec::new(),
+            edge_types: Vec::new(),
+            speed_path: None,
+            damping_path: None,
+        };
+        // Filling vectors with string vectors data
+        for (i, line) in data_array.iter().enumerate() {
+            if line.iter().all(|entry| entry.trim().is_empty()) {
+                continue;
+            }
+            let (x1, y1, x2, y2, edge_type) = parse_edge(line)
+                .map_err(|error| format!("Could not load shape {}: line {}: {}", path, i + 1, error))?;
+            shape.x1.push(x1);
+            shape.y1.push(y1);
+            shape.x2.push(x2);
+            shape.y2.push(y2);
+            shape.edge_types.push(edge_type);
+        }
+        if shape.x1.is_empty() {
+            return Err(format!("Could not load shape {}: it holds no edges", path))
+        }
+        Ok(shape)
+    }
+
+    /// Returns a square with sides of 0.4 around the origin and edges of one type, used by the tests
+    #[cfg(test)]
+    pub fn square(edge_type: EdgeType) -> Shape {
+        Shape {
+            path: String::from("square"),
+            x1: vec![-0.2, 0.2, 0.2, -0.2],
+            y1: vec![-0.2, -0.2, 0.2, 0.2],
+            x2: vec![0.2, 0.2, -0.2, -0.2],
+            y2: vec![-0.2, 0.2, 0.2, -0.2],
+            edge_types: vec![edge_type; 4],
+            speed_path: None,
+            damping_path: None,
+        }
+    }
+}
+
+/// Parses a line of the shape file into the ends of the edge and its boundary type
+fn parse_edge(line: &[String]) -> Result<(f64, f64, f64, f64, EdgeType), String> {
+    let mut coordinates: Vec<f64> = Vec::new();
+    for k in 0..4 {
+        let entry: &str = line.get(k).map(|entry| entry.trim()).unwrap_or("");
+        match f64::from_str(entry) {
+            Ok(value) if value.is_finite() => coordinates.push(value),
+            _ => return Err(format!("expected x1 y1 x2 y2, could not parse {}", if entry.is_empty() {"an empty column"} else {entry})),
+        }
+    }
+    let edge_type: EdgeType = boundary::parse_edge_type(line)?;
+    Ok((coordinates[0], coordinates[1], coordinates[2], coordinates[3], edge_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the error of loading a shape file with the given content
+    fn load_error(name: &str, content: &str) -> String {
+        let path: String = std::env::temp_dir().join(name).to_string_lossy().into_owned();
+        std::fs::write(&path, content).unwrap();
+        let error: String = Shape::load(&path).err().expect("The shape should have been refused");
+        std::fs::remove_file(&path).unwrap();
+        error
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        let error: String = load_error("wave_bad_type.txt", "0 0 1 0\n1 0 1 1 wobbly\n");
+        assert!(error.ends_with("line 2: unknown boundary type wobbly"), "{}", error);
+        let error: String = load_error("wave_bad_pair.txt", "0 0 1 0 periodic\n");
+        assert!(error.ends_with("line 1: periodic edges need a pair id"), "{}", error);
+        let error: String = load_error("wave_bad_number.txt", "0 0 1 0\n\n1 x 1 1\n");
+        assert!(error.ends_with("line 3: expected x1 y1 x2 y2, could not parse x"), "{}", error);
+    }
+}
@@ -0,0 +1,614 @@
+use std::sync::Arc;
+#[cfg(feature = "gui")]
+use druid::{Data, Lens};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use crate::boundary::{self, BoundaryCondition, Wall};
+use crate::cell::{CellKind, CellMask};
+use crate::energy::{self, EnergySeries};
+use crate::field::{FieldLevels, HexField, SharedLevels};
+use crate::functions;
+use crate::medium::{self, PaintedCell};
+use crate::modes::{self, Modes};
+use crate::params::SimulationParams;
+use crate::probe::{Probe, ProbeSeries};
+use crate::scenario::Pulse;
+use crate::shape::Shape;
+use crate::source::{Source, SourceKind};
+
+
+/// Field levels a run started from, see SimulationData::reset
+pub struct Snapshot {
+    pub n: u32,
+    pub previous: HexField,
+    pub current: HexField,
+    /// Displacements added by hand before the run
+    pub pulses: Arc<Vec<Pulse>>,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "gui", derive(Data, Lens))]
+pub struct SimulationData {
+    pub shape: Arc<Shape>,
+    pub params: SimulationParams,
+    /// Range x left and range y down, the grid indices of the origin
+    pub x_off: usize,
+    pub y_off: usize,
+    pub hex_grid: Arc<CellMask>,
+    /// Damping of the sponge layer per unit of wave speed, see boundary::sponge_profile
+    pub hex_sigma: Arc<HexField>,
+    pub hex_speed: Arc<HexField>,
+    /// Speed of the fastest cell inside the shape, see update_max_speed
+    pub max_speed: f64,
+    pub hex_damp: Arc<HexField>,
+    pub damping: f64,
+    pub walls: Arc<Vec<Wall>>,
+    pub boundary: BoundaryCondition,
+    pub sources: Arc<Vec<Source>>,
+    /// Displacements added by hand, kept to save them in a scenario
+    pub pulses: Arc<Vec<Pulse>>,
+    /// Previous, current and next time level of the field
+    pub levels: SharedLevels,
+    pub probes: Arc<Vec<Probe>>,
+    /// Values recorded by the probes since they were placed
+    pub probe_series: Arc<ProbeSeries>,
+    /// Energy and norms of the field after the measured time steps
+    pub energy: Arc<EnergySeries>,
+    /// Measures the energy every nth time step, never for 0 since it costs a pass over the grid
+    pub energy_every: u32,
+    pub n: u32,
+    /// State before the first time step of the run, taken when it is stepped
+    pub initial: Option<Arc<Snapshot>>,
+    /// Counts changes of the field made between time steps
+    pub revision: u32,
+    pub n_max: u32,
+    pub diverged: bool,
+    pub cmap: Arc<[[u8; 3]; 256]>,
+    pub pool: Arc<ThreadPool>,
+}
+
+/// Returns a thread pool with the given number of threads, 0 uses one per core
+fn build_pool(threads: usize) -> Arc<ThreadPool> {
+    Arc::new(ThreadPoolBuilder::new().num_threads(threads).build().expect("Could not start thread pool"))
+}
+
+/// Returns the columns and rows of the hex grid of a shape with the indices of
+/// its origin, the grid reaches three cells beyond the shape on every side
+///
+/// The sums saturate, so a corrupt shape or spacing gives an absurd size
+/// instead of an overflow.
+pub fn grid_layout(shape: &Shape, spacing: f64) -> (usize, usize, usize, usize) {
+    // Find minima and maxima of x and y arays
+    let min_x: f64 = functions::min_element_f64(&shape.x1, &shape.x2);
+    let max_x: f64 = functions::max_element_f64(&shape.x1, &shape.x2);
+    let min_y: f64 = functions::min_element_f64(&shape.y1, &shape.y2);
+    let max_y: f64 = functions::max_element_f64(&shape.y1, &shape.y2);
+    // Settung up boundaries for the array
+    let range_x_left: usize = ((min_x / spacing).abs().ceil() as usize).saturating_add(3);
+    let range_x_right: usize = ((max_x / spacing).abs().ceil() as usize).saturating_add(3);
+    let range_y_down: usize = ((min_y / (spacing*SimulationParams::SI60)).abs().ceil() as usize).saturating_add(3);
+    let range_y_up: usize = ((max_y / (spacing*SimulationParams::SI60)).abs().ceil() as usize).saturating_add(3);
+    let x_len: usize = range_x_left.saturating_add(range_x_right).saturating_add(1);
+    let y_len: usize = range_y_down.saturating_add(range_y_up).saturating_add(1);
+    (x_len, y_len, range_x_left, range_y_down)
+}
+
+impl SimulationData {
+    /// Sets up the hex grid of a shape and an empty field on it
+    pub fn new(shape: Arc<Shape>, params: SimulationParams, cmap: Arc<[[u8; 3]; 256]>) -> SimulationData {
+        let spacing: f64 = params.spacing;
+        // Setting up hex grid
+        let (x_len, y_len, range_x_left, range_y_down) = grid_layout(&shape, spacing);
+        let mut hex_grid: CellMask = HexField::new(x_len, y_len, CellKind::Outside);
+        // Marking the cells inside the boundary
+        for i_y in 0..y_len {
+            let inter = functions::get_vec_intersect((i_y as f64 - range_y_down as f64)*spacing*SimulationParams::SI60,
+                &shape.x1, &shape.y1, &shape.x2, &shape.y2);
+            // Get coordinates and mark inside and outside
+            for i_x in 0..x_len {
+                let (x, _y) = functions::get_cord(i_x, i_y, range_x_left, range_y_down, spacing);
+                let bigger: usize = functions::amount_bigger(x, &inter);
+                if bigger%2 == 1 {
+                    hex_grid[i_y][i_x] = CellKind::Interior;
+                }
+            }
+        }
+        // Assign temporary hex grid
+        let mut temp_hex_grid = hex_grid.clone();
+        // Fill temporary hex grid with the walls around the inside
+        for i in 1..y_len - 2 {
+            for j in 1..x_len - 2 {
+                if hex_grid[i][j] == CellKind::Outside
+                    && functions::neighbour_indices(i, j).iter().any(|&(n_y, n_x)| hex_grid[n_y][n_x].is_active()) {
+                    temp_hex_grid[i][j] = CellKind::Wall;
+                }
+            }
+        }
+        // Moving temporary back into normal hex grid
+        hex_grid = temp_hex_grid;
+        // Tagging the wall cells and setting up the sponge layer
+        let mut walls: Vec<Wall> = boundary::find_walls(&hex_grid);
+        boundary::tag_walls(&mut walls, &hex_grid, &shape, range_x_left, range_y_down, spacing);
+        let hex_sigma: HexField = boundary::sponge_profile(&hex_grid, &walls, spacing);
+        // Loading the wave speed map if there is one, otherwise the speed is uniform
+        let mut hex_speed: HexField = HexField::new(x_len, y_len, params.speed);
+        if let Some(path) = &shape.speed_path {
+            let (values, regions) = medium::region_map(path, x_len, y_len, range_x_left, range_y_down, spacing);
+            hex_speed = medium::fill_regions(&values, &regions, params.speed);
+            // Every region of the speed map is a material of its own
+            for i_y in 0..y_len {
+                for i_x in 0..x_len {
+                    if let (CellKind::Interior, Some(id)) = (hex_grid[i_y][i_x], regions[i_y][i_x]) {
+                        hex_grid[i_y][i_x] = CellKind::Material(id);
+                    }
+                }
+            }
+        }
+        // Loading the damping map the same way, without one there is no local damping
+        let hex_damp: HexField = match &shape.damping_path {
+            Some(path) => medium::load_regions(path, x_len, y_len, range_x_left, range_y_down, spacing, 0.0),
+            None => HexField::new(x_len, y_len, 0.0),
+        };
+        // Shortening the time step if the fastest cell would be unstable
+        let max_speed: f64 = medium::max_inside(&hex_speed, &hex_grid);
+        let stable_params: SimulationParams = params.stabilized(max_speed);
+        if stable_params.time_spacing < params.time_spacing {
+            println!("Time spacing reduced to {:.5} to satisfy the stability limit", stable_params.time_spacing);
+        }
+
+        SimulationData{
+            shape,
+            params: stable_params,
+            x_off: range_x_left,
+            y_off: range_y_down,
+            hex_grid: Arc::new(hex_grid),
+            hex_sigma: Arc::new(hex_sigma),
+            hex_speed: Arc::new(hex_speed),
+            max_speed,
+            hex_damp: Arc::new(hex_damp),
+            damping: 0.0,
+            walls: Arc::new(walls),
+            boundary: BoundaryCondition::Reflecting,
+            sources: Arc::new(Vec::new()),
+            pulses: Arc::new(Vec::new()),
+            levels: SharedLevels::new(FieldLevels::new(x_len, y_len)),
+            probes: Arc::new(Vec::new()),
+            probe_series: Arc::new(ProbeSeries::new()),
+            energy: Arc::new(EnergySeries::new()),
+            energy_every: 0,
+            n: 0,
+            initial: None,
+            revision: 0,
+            n_max: 3000,
+            diverged: false,
+            cmap,
+            pool: build_pool(stable_params.threads)}
+    }
+
+    /// Builds the simulation anew with changed parameters
+    ///
+    /// The field is reset, the boundary condition, damping and the sources are
+    /// carried over. Painted speed and damping are resampled onto the new grid,
+    /// every new cell takes the painted value of the old cell at its coordinates.
+    pub fn rebuild(&self, params: SimulationParams) -> SimulationData {
+        let mut rebuilt: SimulationData = SimulationData::new(self.shape.clone(), params, self.cmap.clone());
+        rebuilt.boundary = self.boundary;
+        rebuilt.damping = self.damping;
+        rebuilt.n_max = self.n_max;
+        // Moving what was painted to the cells at the same coordinates, the rest follows the files and the new speed
+        let (file_speed, file_damp) = self.file_maps();
+        {
+            let hex_speed = Arc::make_mut(&mut rebuilt.hex_speed);
+            let hex_damp = Arc::make_mut(&mut rebuilt.hex_damp);
+            for i_y in 0..rebuilt.hex_grid.y_len() {
+                for i_x in 0..rebuilt.hex_grid.x_len() {
+                    if !rebuilt.hex_grid[i_y][i_x].is_active() {
+                        continue;
+                    }
+                    let (x, y) = functions::get_cord(i_x, i_y, rebuilt.x_off, rebuilt.y_off, params.spacing);
+                    if let Some((old_x, old_y)) = self.cell_at(x, y) {
+                        if self.hex_speed[old_y][old_x] != file_speed[old_y][old_x] {
+                            hex_speed[i_y][i_x] = self.hex_speed[old_y][old_x];
+                        }
+                        if self.hex_damp[old_y][old_x] != file_damp[old_y][old_x] {
+                            hex_damp[i_y][i_x] = self.hex_damp[old_y][old_x];
+                        }
+                    }
+                }
+            }
+        }
+        // The painted speed can be faster than the new time step allows
+        rebuilt.update_max_speed();
+        rebuilt.set_time_spacing(params.time_spacing);
+        // Moving the sources to the cells at the same coordinates
+        for source in self.sources.iter() {
+            let (x, y) = functions::get_cord(source.i_x, source.i_y, self.x_off, self.y_off, self.params.spacing);
+            if let Some((i_x, i_y)) = rebuilt.cell_at(x, y) {
+                if rebuilt.hex_grid[i_y][i_x].is_active() {
+                    let mut moved: Source = source.clone();
+                    moved.i_x = i_x;
+                    moved.i_y = i_y;
+                    rebuilt.place_source(moved);
+                }
+            }
+        }
+        for probe in self.probes.iter() {
+            let (x, y) = functions::get_cord(probe.i_x, probe.i_y, self.x_off, self.y_off, self.params.spacing);
+            if let Some((i_x, i_y)) = rebuilt.cell_at(x, y) {
+                rebuilt.place_probe(i_x, i_y);
+            }
+        }
+        rebuilt
+    }
+
+    pub fn calc_next_frame(&mut self) {
+        let params: SimulationParams = self.params;
+        let mut levels = self.levels.lock();
+        // Keeping the state the run starts from, again after every reset
+        if self.initial.as_ref().is_none_or(|initial| initial.n == self.n) {
+            self.initial = Some(Arc::new(Snapshot{n: self.n, previous: levels.previous().clone(), current: levels.current().clone(),
+                pulses: self.pulses.clone()}));
+        }
+        // Largest displacement of the updated cells, infinite once one of them is not a number
+        let peak: f64;
+        {
+            let (hex_tnm1, hex_tn, hex_temp) = levels.step_mut();
+            let hex_grid = &self.hex_grid;
+            let hex_speed = &self.hex_speed;
+            let hex_damp = &self.hex_damp;
+            let hex_sigma = &self.hex_sigma;
+            let damping: f64 = self.damping;
+            let sponge_active: bool = self.boundary == BoundaryCondition::Sponge;
+            let x_len: usize = hex_grid.x_len();
+            let stride: usize = hex_tn.stride();
+            let tn: &[f64] = hex_tn.data();
+            let tnm1: &[f64] = hex_tnm1.data();
+
+            // Every row only writes to itself, so the rows are updated in parallel
+            peak = self.pool.install(|| {
+                hex_temp.par_rows_mut().with_min_len(8).map(|(i_y, row)| {
+                    // Odd rows are shifted right, their upper and lower neighbours one cell further right
+                    let shift: usize = i_y%2;
+                    let mut row_peak: f64 = 0.0;
+                    for i_x in 0..x_len {
+                        if hex_grid[i_y][i_x].is_active() {
+                            let courant_sq: f64 = params.courant(hex_speed[i_y][i_x]).powi(2);
+                            let c: usize = hex_tn.flat_index(i_x, i_y);
+                            let laplace: f64 = 2.0/3.0*(tn[c-stride-1+shift] + tn[c-stride+shift] + tn[c-1] + tn[c+1] +
+                                tn[c+stride-1+shift] + tn[c+stride+shift]) - 4.0*tn[c];
+                            // Damped leapfrog step for u_tt + gamma*u_t = c^2*laplace(u), the
+                            // sponge layer adds to the damping of the medium in proportion to the local speed
+                            let mut gamma: f64 = damping + hex_damp[i_y][i_x];
+                            if sponge_active {
+                                gamma += hex_sigma[i_y][i_x]*hex_speed[i_y][i_x];
+                            }
+                            let damp: f64 = 0.5*gamma*params.time_spacing;
+                            let value: f64 = (2.0*tn[c] - (1.0 - damp)*tnm1[c] + courant_sq*laplace) / (1.0 + damp);
+                            row[i_x] = value;
+                            row_peak = row_peak.max(if value.is_nan() {f64::INFINITY} else {value.abs()});
+                        }
+                    }
+                    row_peak
+                }).reduce(|| 0.0, f64::max)
+            });
+            // Driving the sources, the signal is added to the Laplacian of its cell
+            let time: f64 = self.n as f64*params.time_spacing;
+            for source in self.sources.iter().filter(|source| source.active) {
+                if self.hex_grid[source.i_y][source.i_x] == CellKind::Source {
+                    let courant_sq: f64 = params.courant(self.hex_speed[source.i_y][source.i_x]).powi(2);
+                    hex_temp[source.i_y][source.i_x] += courant_sq*source.signal(time);
+                }
+            }
+            boundary::apply_walls(self.boundary, &self.walls, &params, &self.hex_speed, hex_tn, hex_temp);
+        }
+        levels.advance();
+        self.n += 1;
+        Arc::make_mut(&mut self.probe_series).record(self.n as f64*params.time_spacing, &self.probes, levels.current());
+        if self.energy_every > 0 && self.n.is_multiple_of(self.energy_every) {
+            Arc::make_mut(&mut self.energy).record(energy::measure(levels.previous(), levels.current(), &self.hex_grid, &self.hex_speed,
+                &params, &self.pool, self.n as f64*params.time_spacing));
+        }
+        // Catching a blow up of the explicit scheme, found while updating the field
+        if !peak.is_finite() || peak > functions::DIVERGENCE_LIMIT {
+            self.diverged = true;
+        }
+    }
+
+    /// Returns a copy with its own field levels
+    ///
+    /// The recordings of the probes and the energy are copied on write like the
+    /// maps, so neither copy sees what the other records.
+    pub fn detached(&self) -> SimulationData {
+        let mut copy: SimulationData = self.clone();
+        copy.levels = self.levels.detached();
+        copy
+    }
+
+    /// Returns the indices of the cell closest to the given coordinates if it lies on the grid
+    pub fn cell_at(&self, x: f64, y: f64) -> Option<(usize, usize)> {
+        functions::get_index(x, y, self.x_off, self.y_off, self.params.spacing)
+            .filter(|&(i_x, i_y)| i_x < self.hex_grid.x_len() && i_y < self.hex_grid.y_len())
+    }
+
+    /// Returns the speed and damping maps given by the files of the shape, without what was painted since
+    pub fn file_maps(&self) -> (HexField, HexField) {
+        let (x_len, y_len) = (self.hex_grid.x_len(), self.hex_grid.y_len());
+        let spacing: f64 = self.params.spacing;
+        let hex_speed: HexField = match &self.shape.speed_path {
+            Some(path) => medium::load_regions(path, x_len, y_len, self.x_off, self.y_off, spacing, self.params.speed),
+            None => HexField::new(x_len, y_len, self.params.speed),
+        };
+        let hex_damp: HexField = match &self.shape.damping_path {
+            Some(path) => medium::load_regions(path, x_len, y_len, self.x_off, self.y_off, spacing, 0.0),
+            None => HexField::new(x_len, y_len, 0.0),
+        };
+        (hex_speed, hex_damp)
+    }
+
+    /// Returns the cells of the speed and damping maps that were painted, with
+    /// their coordinates and values, see file_maps
+    pub fn painted_cells(&self) -> (Vec<PaintedCell>, Vec<PaintedCell>) {
+        let (file_speed, file_damp) = self.file_maps();
+        let mut speed_cells: Vec<PaintedCell> = Vec::new();
+        let mut damping_cells: Vec<PaintedCell> = Vec::new();
+        for i_y in 0..self.hex_grid.y_len() {
+            for i_x in 0..self.hex_grid.x_len() {
+                if !self.hex_grid[i_y][i_x].is_active() {
+                    continue;
+                }
+                let (x, y) = functions::get_cord(i_x, i_y, self.x_off, self.y_off, self.params.spacing);
+                if self.hex_speed[i_y][i_x] != file_speed[i_y][i_x] {
+                    speed_cells.push((x, y, self.hex_speed[i_y][i_x]));
+                }
+                if self.hex_damp[i_y][i_x] != file_damp[i_y][i_x] {
+                    damping_cells.push((x, y, self.hex_damp[i_y][i_x]));
+                }
+            }
+        }
+        (speed_cells, damping_cells)
+    }
+
+    /// Returns the Courant number of the fastest cell inside the shape
+    pub fn max_courant(&self) -> f64 {
+        self.params.courant(self.max_speed)
+    }
+
+    /// Finds the fastest cell inside the shape again, called after every change of the speed map
+    pub fn update_max_speed(&mut self) {
+        self.max_speed = medium::max_inside(&self.hex_speed, &self.hex_grid);
+    }
+
+    /// Changes the number of threads updating the field
+    pub fn set_threads(&mut self, threads: usize) {
+        self.params.threads = threads;
+        self.pool = build_pool(threads);
+    }
+
+    /// Changes the time step, shortened if needed to stay stable
+    pub fn set_time_spacing(&mut self, time_spacing: f64) {
+        let mut params: SimulationParams = self.params;
+        params.time_spacing = time_spacing;
+        self.params = params.stabilized(self.max_speed);
+    }
+
+    pub fn paint_speed(&mut self, x_perc_pos: f64, y_perc_pos: f64, radius: f64, speed: f64) {
+        let spacing: f64 = self.params.spacing;
+        // Clamping to the fastest speed the time step can handle
+        let max_speed: f64 = self.params.max_stable_speed();
+        let hex_speed = Arc::make_mut(&mut self.hex_speed);
+        let (x_pos, y_pos) = functions::picture_cell(x_perc_pos, y_perc_pos, hex_speed.x_len(), hex_speed.y_len());
+        medium::paint_disc(hex_speed, x_pos, y_pos, radius, spacing, speed.min(max_speed));
+        self.update_max_speed();
+    }
+
+    pub fn paint_damping(&mut self, x_perc_pos: f64, y_perc_pos: f64, radius: f64, damping: f64) {
+        let spacing: f64 = self.params.spacing;
+        let hex_damp = Arc::make_mut(&mut self.hex_damp);
+        let (x_pos, y_pos) = functions::picture_cell(x_perc_pos, y_perc_pos, hex_damp.x_len(), hex_damp.y_len());
+        medium::paint_disc(hex_damp, x_pos, y_pos, radius, spacing, damping);
+    }
+
+    pub fn add_source(&mut self, x_perc_pos: f64, y_perc_pos: f64, kind: SourceKind, amplitude: f64, frequency: f64) {
+        let (x_pos, y_pos) = functions::picture_cell(x_perc_pos, y_perc_pos, self.hex_grid.x_len(), self.hex_grid.y_len());
+
+        if !self.hex_grid[y_pos][x_pos].is_active() {
+            println!("Outside");
+            return;
+        }
+        match Source::new(kind, x_pos, y_pos, amplitude, frequency) {
+            Ok(source) => self.place_source(source),
+            Err(error) => println!("Could not add source: {}", error),
+        }
+    }
+
+    /// Adds a source and marks its cell, the cell has to be inside the shape
+    pub fn place_source(&mut self, mut source: Source) {
+        let hex_grid = Arc::make_mut(&mut self.hex_grid);
+        source.cell = hex_grid[source.i_y][source.i_x];
+        hex_grid[source.i_y][source.i_x] = CellKind::Source;
+        Arc::make_mut(&mut self.sources).push(source);
+    }
+
+    /// Removes all sources and gives their cells back their former kind
+    pub fn clear_sources(&mut self) {
+        let hex_grid = Arc::make_mut(&mut self.hex_grid);
+        // Going backwards, so stacked sources restore the cell below the first one
+        for source in self.sources.iter().rev() {
+            hex_grid[source.i_y][source.i_x] = source.cell;
+        }
+        self.sources = Arc::new(Vec::new());
+    }
+
+    pub fn add_probe(&mut self, x_perc_pos: f64, y_perc_pos: f64) {
+        let (x_pos, y_pos) = functions::picture_cell(x_perc_pos, y_perc_pos, self.hex_grid.x_len(), self.hex_grid.y_len());
+        self.place_probe(x_pos, y_pos);
+    }
+
+    /// Adds a probe to a cell inside the shape, the recordings of all probes
+    /// start anew from the current time step
+    pub fn place_probe(&mut self, x_pos: usize, y_pos: usize) {
+        if self.hex_grid[y_pos][x_pos].is_active() {
+            Arc::make_mut(&mut self.probes).push(Probe{i_x: x_pos, i_y: y_pos});
+            self.restart_probes();
+        } else {
+            println!("Outside");
+        }
+    }
+
+    pub fn clear_probes(&mut self) {
+        self.probes = Arc::new(Vec::new());
+        self.restart_probes();
+    }
+
+    /// Drops the recorded probe values and records the current time step
+    fn restart_probes(&mut self) {
+        let mut series: ProbeSeries = ProbeSeries::new();
+        series.clear(&self.probes);
+        series.record(self.n as f64*self.params.time_spacing, &self.probes, self.levels.lock().current());
+        self.probe_series = Arc::new(series);
+    }
+
+    /// Returns the coordinates of the probes
+    pub fn probe_coordinates(&self) -> Vec<(f64, f64)> {
+        self.probes.iter().map(|probe| functions::get_cord(probe.i_x, probe.i_y, self.x_off, self.y_off, self.params.spacing)).collect()
+    }
+
+    pub fn add_initial(&mut self, x_perc_pos: f64, y_perc_pos: f64, height: f64) {
+        let (x_pos, y_pos) = functions::picture_cell(x_perc_pos, y_perc_pos, self.hex_grid.x_len(), self.hex_grid.y_len());
+        self.add_point(x_pos, y_pos, height);
+    }
+
+    /// Displaces a single cell of the current time level
+    pub fn add_point(&mut self, x_pos: usize, y_pos: usize, height: f64) {
+        if self.hex_grid[y_pos][x_pos].is_active() {
+            self.levels.lock().current_mut()[y_pos][x_pos] += height;
+            self.revision += 1;
+            let (x, y) = functions::get_cord(x_pos, y_pos, self.x_off, self.y_off, self.params.spacing);
+            Arc::make_mut(&mut self.pulses).push(Pulse::Point {x, y, height});
+        } else {
+            println!("Outside");
+        }
+    }
+
+    pub fn add_initial_gauss(&mut self, x_perc_pos: f64, y_perc_pos: f64, stdv: f64, height: f64) {
+        let (x_pos, y_pos) = functions::picture_cell(x_perc_pos, y_perc_pos, self.hex_grid.x_len(), self.hex_grid.y_len());
+        self.add_gauss(x_pos, y_pos, stdv, height);
+    }
+
+    /// Adds a Gaussian centered on a cell to the current time level
+    pub fn add_gauss(&mut self, x_pos: usize, y_pos: usize, stdv: f64, height: f64) {
+        let (x, y) = functions::get_cord(x_pos, y_pos, self.x_off, self.y_off, self.params.spacing);
+        Arc::make_mut(&mut self.pulses).push(Pulse::Gauss {x, y, width: stdv, height});
+        let hex_grid = &self.hex_grid;
+        let mut levels = self.levels.lock();
+        let hex_tn = levels.current_mut();
+        self.revision += 1;
+
+        for iy in 0..hex_grid.y_len() {
+            for ix in 0..hex_grid.x_len() {
+                if hex_grid[iy][ix].is_active() {
+                    let dist: f64 = functions::grid_distance(x_pos, y_pos, ix, iy, 10, 10, self.params.spacing);
+                    hex_tn[iy][ix] += functions::gaussian(height, stdv, dist);
+                }
+            }
+        }
+    }
+
+    /// Returns the count lowest eigenmodes of the grid, see modes::solve
+    pub fn modes(&self, count: usize) -> Modes {
+        modes::solve(&self.hex_grid, &self.hex_speed, &self.params, count)
+    }
+
+    /// Replaces the field with a standing wave of an eigenmode at its largest
+    /// displacement, see modes::solve
+    ///
+    /// The previous level is the mode one time step earlier, so without damping
+    /// the field oscillates as amplitude*cos(2 pi f t) times the mode shape. The
+    /// probe recordings start anew.
+    pub fn start_from_mode(&mut self, mode: &HexField, frequency: f64, amplitude: f64) {
+        let phase: f64 = (2.0*std::f64::consts::PI*frequency*self.params.time_spacing).cos();
+        {
+            let mut levels = self.levels.lock();
+            for i_y in 0..self.hex_grid.y_len() {
+                for i_x in 0..self.hex_grid.x_len() {
+                    let value: f64 = if self.hex_grid[i_y][i_x].is_active() {amplitude*mode[i_y][i_x]} else {0.0};
+                    levels.current_mut()[i_y][i_x] = value;
+                    levels.previous_mut()[i_y][i_x] = phase*value;
+                }
+            }
+        }
+        self.revision += 1;
+        self.initial = None;
+        self.pulses = Arc::new(Vec::new());
+        self.restart_probes();
+    }
+
+    /// Returns the field to the state before the first time step of the run
+    ///
+    /// Edits made since then are undone, the sources start over and the probe
+    /// and energy recordings are dropped.
+    pub fn reset(&mut self) {
+        if let Some(initial) = self.initial.clone() {
+            let mut levels = self.levels.lock();
+            *levels.previous_mut() = initial.previous.clone();
+            *levels.current_mut() = initial.current.clone();
+            self.n = initial.n;
+            self.pulses = initial.pulses.clone();
+        }
+        self.diverged = false;
+        self.revision += 1;
+        self.energy = Arc::new(EnergySeries::new());
+        self.restart_probes();
+    }
+
+    /// Sets the field to rest at the current time step, which also recovers a diverged run
+    pub fn clear_field(&mut self) {
+        {
+            let mut levels = self.levels.lock();
+            levels.previous_mut().fill(0.0);
+            levels.current_mut().fill(0.0);
+        }
+        self.pulses = Arc::new(Vec::new());
+        self.diverged = false;
+        self.revision += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary::EdgeType;
+
+    #[test]
+    fn rebuild_keeps_the_painted_maps() {
+        let shape: Shape = Shape::square(EdgeType::Default);
+        let params = SimulationParams{speed: 1.0, spacing: 0.01, time_spacing: 0.002, threads: 1};
+        let mut sim: SimulationData = SimulationData::new(Arc::new(shape), params, Arc::new([[0; 3]; 256]));
+        sim.paint_speed(0.5, 0.5, 0.05, 0.5);
+        sim.paint_damping(0.5, 0.5, 0.05, 3.0);
+        let rebuilt: SimulationData = sim.rebuild(SimulationParams{speed: 2.0, spacing: 0.005, ..params});
+        // The centre keeps what was painted, the rest takes the new speed
+        let (i_x, i_y) = rebuilt.cell_at(0.0, 0.0).unwrap();
+        assert_eq!(rebuilt.hex_speed[i_y][i_x], 0.5);
+        assert_eq!(rebuilt.hex_damp[i_y][i_x], 3.0);
+        let (i_x, i_y) = rebuilt.cell_at(0.15, 0.15).unwrap();
+        assert_eq!(rebuilt.hex_speed[i_y][i_x], 2.0);
+        assert_eq!(rebuilt.hex_damp[i_y][i_x], 0.0);
+        assert_eq!(rebuilt.max_speed, 2.0);
+        assert!(rebuilt.params.time_spacing <= functions::CFL_LIMIT*0.005/2.0);
+    }
+
+    #[test]
+    fn catches_a_blow_up_while_stepping() {
+        let shape: Shape = Shape::square(EdgeType::Default);
+        let params = SimulationParams{speed: 1.0, spacing: 0.01, time_spacing: 0.002, threads: 1};
+        let mut sim: SimulationData = SimulationData::new(Arc::new(shape), params, Arc::new([[0; 3]; 256]));
+        let (i_x, i_y) = sim.cell_at(0.0, 0.0).unwrap();
+        sim.add_point(i_x, i_y, 1.0);
+        sim.calc_next_frame();
+        assert!(!sim.diverged);
+        sim.add_point(i_x, i_y, f64::NAN);
+        sim.calc_next_frame();
+        assert!(sim.diverged);
+    }
+}
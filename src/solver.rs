@@ -0,0 +1,488 @@
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use druid::{Data, ExtEventSink, Selector};
+use crate::boundary::BoundaryCondition;
+use crate::cell::CellMask;
+use crate::export::FieldSeries;
+use crate::field::{HexField, SharedLevels};
+use crate::recording::Recorder;
+use crate::simulation::SimulationData;
+use crate::source::{Source, SourceKind};
+
+/// Delivers the frames of the solver thread to the window
+pub const FRAME_READY: Selector<Frame> = Selector::new("wave.frame-ready");
+
+/// How the solver thread steps the simulation, set from the controls of the window
+#[derive(Clone, PartialEq)]
+pub struct Settings {
+    pub running: bool,
+    /// Time steps computed between two frames
+    pub steps_per_frame: u32,
+    pub frame_interval: Duration,
+    /// Time step the run pauses at
+    pub limit: Option<u32>,
+    /// File, every nth time step, scale and smooth shading of an open recording
+    pub record: Option<(String, u32, usize, bool)>,
+    /// File and every nth time step of an open field export
+    pub export: Option<(String, u32)>,
+    /// Measures the energy every nth time step, see SimulationData::energy_every
+    pub energy_every: u32,
+}
+
+/// Number of frames whose field levels are kept to be reused, see Worker::publish
+const SPARE_LEVELS: usize = 4;
+
+/// Change made to the simulation in the window
+///
+/// The window applies it to its own copy and sends it to the solver thread,
+/// which applies it to the state it has computed since, so no time step or
+/// recorded sample is lost. Positions are fractions of the picture as the
+/// cursor gives them.
+#[derive(Clone)]
+pub enum Edit {
+    /// Position and height of a displaced cell, see SimulationData::add_initial
+    Point(f64, f64, f64),
+    /// Position, width and height of a Gaussian, see SimulationData::add_initial_gauss
+    Gauss(f64, f64, f64, f64),
+    /// Position, radius and value of a painted disc
+    PaintSpeed(f64, f64, f64, f64),
+    PaintDamping(f64, f64, f64, f64),
+    /// Position, kind, amplitude and frequency of a new source
+    AddSource(f64, f64, SourceKind, f64, f64),
+    PlaceSource(Source),
+    /// Sources with the settings changed in the side panel, on the same cells
+    Sources(Arc<Vec<Source>>),
+    ClearSources,
+    AddProbe(f64, f64),
+    ClearProbes,
+    /// Shape, frequency and amplitude of an eigenmode, see SimulationData::start_from_mode
+    StartFromMode(HexField, f64, f64),
+    Reset,
+    ClearField,
+    Boundary(BoundaryCondition),
+    Damping(f64),
+    StepLimit(u32),
+    TimeSpacing(f64),
+    Threads(usize),
+}
+
+impl Edit {
+    pub fn apply(&self, sim: &mut SimulationData) {
+        match self {
+            Edit::Point(x, y, height) => sim.add_initial(*x, *y, *height),
+            Edit::Gauss(x, y, width, height) => sim.add_initial_gauss(*x, *y, *width, *height),
+            Edit::PaintSpeed(x, y, radius, speed) => sim.paint_speed(*x, *y, *radius, *speed),
+            Edit::PaintDamping(x, y, radius, damping) => sim.paint_damping(*x, *y, *radius, *damping),
+            Edit::AddSource(x, y, kind, amplitude, frequency) => sim.add_source(*x, *y, *kind, *amplitude, *frequency),
+            Edit::PlaceSource(source) => sim.place_source(source.clone()),
+            Edit::Sources(sources) => sim.sources = sources.clone(),
+            Edit::ClearSources => sim.clear_sources(),
+            Edit::AddProbe(x, y) => sim.add_probe(*x, *y),
+            Edit::ClearProbes => sim.clear_probes(),
+            Edit::StartFromMode(mode, frequency, amplitude) => sim.start_from_mode(mode, *frequency, *amplitude),
+            Edit::Reset => sim.reset(),
+            Edit::ClearField => sim.clear_field(),
+            Edit::Boundary(boundary) => sim.boundary = *boundary,
+            Edit::Damping(damping) => sim.damping = *damping,
+            Edit::StepLimit(n_max) => sim.n_max = *n_max,
+            Edit::TimeSpacing(time_spacing) => sim.set_time_spacing(*time_spacing),
+            Edit::Threads(threads) => sim.set_threads(*threads),
+        }
+    }
+}
+
+enum SolverCommand {
+    /// Replaces the simulation, the time steps computed so far are dropped
+    Load(SimulationData),
+    Edit(Edit),
+    Configure(Settings),
+    /// Runs the given number of time steps in addition to the pending ones
+    Step(u32),
+    /// Drops the pending time steps
+    CancelSteps,
+}
+
+/// State of the solver thread after a frame
+pub struct Frame {
+    /// Number of simulations and edits the thread had received, the window
+    /// drops the simulation of frames computed before its last change arrived
+    pub changes: u32,
+    /// Copy of the simulation with its own field levels
+    pub sim: SimulationData,
+    /// Set if the run paused itself at its step limit or on divergence
+    pub paused: bool,
+    /// Set if the recording or the export was closed without being asked to
+    /// since the last frame, which happens when the grid is replaced or a
+    /// file cannot be written
+    pub recording_closed: bool,
+    pub export_closed: bool,
+    /// Errors of the recording and the export since the last frame, and how
+    /// much they wrote once closed
+    pub errors: Vec<String>,
+    pub steps_per_second: f64,
+}
+
+/// Handle of the thread stepping the simulation
+///
+/// The thread owns its own copy of the simulation with the recordings of the
+/// probes and the energy, and the open recording and export, so the window
+/// stays responsive however long a time step takes. Frames come back through
+/// the event sink of the window, changes made in the window are sent as edits.
+#[derive(Clone)]
+pub struct Solver(Arc<Sender<SolverCommand>>);
+
+impl Solver {
+    /// Starts the thread, idle until a simulation is loaded and run
+    pub fn start(sink: ExtEventSink) -> Solver {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || Worker::new(sink).run(receiver));
+        Solver(Arc::new(sender))
+    }
+
+    /// Replaces the simulation of the thread, frames computed before are still
+    /// delivered, see Frame::changes
+    pub fn load(&self, sim: SimulationData) {
+        self.send(SolverCommand::Load(sim));
+    }
+
+    /// Applies a change to the simulation of the thread, see Edit
+    pub fn edit(&self, edit: Edit) {
+        self.send(SolverCommand::Edit(edit));
+    }
+
+    pub fn configure(&self, settings: Settings) {
+        self.send(SolverCommand::Configure(settings));
+    }
+
+    pub fn step(&self, count: u32) {
+        self.send(SolverCommand::Step(count));
+    }
+
+    pub fn cancel_steps(&self) {
+        self.send(SolverCommand::CancelSteps);
+    }
+
+    fn send(&self, command: SolverCommand) {
+        if self.0.send(command).is_err() {
+            println!("The solver thread has stopped, the simulation no longer runs");
+        }
+    }
+}
+
+impl Data for Solver {
+    fn same(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+struct Worker {
+    sink: ExtEventSink,
+    sim: Option<SimulationData>,
+    /// Number of simulations and edits received, see Frame::changes
+    changes: u32,
+    /// Set if the simulation was edited since the last frame
+    edited: bool,
+    settings: Settings,
+    /// Time steps still to run while paused
+    pending: u32,
+    next_frame: Instant,
+    /// Open recording with the grid and the file it was started on
+    recorder: Option<(Recorder, Arc<CellMask>, String)>,
+    /// Open field export with the grid and the file it was started on
+    series: Option<(FieldSeries, Arc<CellMask>, String)>,
+    /// Requests closed because the grid was replaced, not opened again until
+    /// the window has seen that
+    closed_record: Option<(String, u32, usize, bool)>,
+    closed_export: Option<(String, u32)>,
+    /// Field levels of the last frames, copied into again once the window let go of them
+    spare_levels: Vec<SharedLevels>,
+    /// Start and number of time steps of the current steps per second measurement
+    rate_start: Instant,
+    rate_steps: u32,
+    steps_per_second: f64,
+    /// Events not delivered with a frame yet, see Frame
+    paused: bool,
+    recording_closed: bool,
+    export_closed: bool,
+    errors: Vec<String>,
+}
+
+impl Worker {
+    fn new(sink: ExtEventSink) -> Worker {
+        Worker {
+            sink,
+            sim: None,
+            changes: 0,
+            edited: false,
+            settings: Settings {
+                running: false,
+                steps_per_frame: 1,
+                frame_interval: Duration::from_millis(50),
+                limit: None,
+                record: None,
+                export: None,
+                energy_every: 0,
+            },
+            pending: 0,
+            next_frame: Instant::now(),
+            recorder: None,
+            series: None,
+            closed_record: None,
+            closed_export: None,
+            spare_levels: Vec::new(),
+            rate_start: Instant::now(),
+            rate_steps: 0,
+            steps_per_second: 0.0,
+            paused: false,
+            recording_closed: false,
+            export_closed: false,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Handles commands until the window is gone, computing a frame whenever one is due
+    fn run(mut self, receiver: Receiver<SolverCommand>) {
+        loop {
+            // An idle thread waits for the next command, a running one at most until the next frame
+            let command = if self.active() {
+                receiver.recv_timeout(self.next_frame.saturating_duration_since(Instant::now()))
+            } else if self.edited {
+                // A paused run shows its edits in a frame once those already sent are applied
+                receiver.try_recv().map_err(|_| RecvTimeoutError::Timeout)
+            } else {
+                receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            };
+            match command {
+                Ok(command) => self.handle(command),
+                Err(RecvTimeoutError::Timeout) => self.frame(),
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+
+    fn handle(&mut self, command: SolverCommand) {
+        let was_active: bool = self.active();
+        match command {
+            SolverCommand::Load(sim) => {
+                // The time steps computed past the loaded state are dropped with their recordings
+                self.sim = Some(sim.detached());
+                self.changes += 1;
+                self.edited = false;
+                self.sync_outputs();
+                // The window already shows the loaded state, only what happened to the outputs is news
+                if self.undelivered() {
+                    self.publish();
+                }
+            }
+            SolverCommand::Edit(edit) => {
+                // The window may not have seen the time steps the edit is applied after
+                if let Some(sim) = &mut self.sim {
+                    edit.apply(sim);
+                    self.edited = true;
+                }
+                self.changes += 1;
+                self.sync_outputs();
+            }
+            SolverCommand::Configure(settings) => {
+                self.settings = settings;
+                self.sync_outputs();
+                if self.settings.running && !self.active() {
+                    self.pause();
+                }
+                if self.undelivered() {
+                    self.publish();
+                }
+            }
+            SolverCommand::Step(count) => self.pending += count,
+            SolverCommand::CancelSteps => self.pending = 0,
+        }
+        if self.active() && !was_active {
+            self.next_frame = Instant::now() + self.settings.frame_interval;
+            self.rate_start = Instant::now();
+            self.rate_steps = 0;
+        }
+    }
+
+    /// Returns true if the run has time steps to compute
+    fn active(&self) -> bool {
+        match &self.sim {
+            Some(sim) => (self.settings.running || self.pending > 0) && !sim.diverged && !self.at_limit(sim),
+            None => false,
+        }
+    }
+
+    fn at_limit(&self, sim: &SimulationData) -> bool {
+        self.settings.limit.is_some_and(|limit| sim.n >= limit)
+    }
+
+    /// Returns true if there are events the window has not seen yet, see Frame
+    fn undelivered(&self) -> bool {
+        self.paused || self.recording_closed || self.export_closed || !self.errors.is_empty()
+    }
+
+    fn pause(&mut self) {
+        self.settings.running = false;
+        self.pending = 0;
+        self.paused = true;
+    }
+
+    /// Computes the time steps of one frame, recording every one of them, and
+    /// delivers the last
+    fn frame(&mut self) {
+        self.next_frame += self.settings.frame_interval;
+        // A frame taking longer than the target is followed right away
+        self.next_frame = self.next_frame.max(Instant::now());
+        self.sync_outputs();
+        let mut steps: u32 = 0;
+        while steps < self.settings.steps_per_frame && self.active() {
+            let sim: &mut SimulationData = self.sim.as_mut().unwrap();
+            self.pending = self.pending.saturating_sub(1);
+            sim.calc_next_frame();
+            steps += 1;
+            if let Some((recorder, _grid, _path)) = &mut self.recorder {
+                if let Err(error) = recorder.record(sim) {
+                    self.recorder = None;
+                    self.closed_record = self.settings.record.clone();
+                    self.recording_closed = true;
+                    self.errors.push(error);
+                }
+            }
+            if let Some((series, _grid, _path)) = &mut self.series {
+                if let Err(error) = series.record(sim) {
+                    self.series = None;
+                    self.closed_export = self.settings.export.clone();
+                    self.export_closed = true;
+                    self.errors.push(error);
+                }
+            }
+            if sim.diverged || self.settings.limit.is_some_and(|limit| sim.n >= limit) {
+                self.pause();
+            }
+        }
+        self.measure_rate(steps);
+        self.publish();
+    }
+
+    /// Updates the time steps per second about twice a second
+    fn measure_rate(&mut self, steps: u32) {
+        self.rate_steps += steps;
+        let elapsed: f64 = self.rate_start.elapsed().as_secs_f64();
+        if elapsed >= 0.5 {
+            self.steps_per_second = self.rate_steps as f64/elapsed;
+            self.rate_start = Instant::now();
+            self.rate_steps = 0;
+        }
+        if !self.active() {
+            self.steps_per_second = 0.0;
+        }
+    }
+
+    /// Starts and stops the recording and the export as requested, both also
+    /// stop when the grid is replaced since their frames would change their size
+    fn sync_outputs(&mut self) {
+        let sim: &SimulationData = match &mut self.sim {
+            Some(sim) => {
+                sim.energy_every = self.settings.energy_every;
+                sim
+            }
+            None => return,
+        };
+        if self.settings.record.is_none() {
+            self.closed_record = None;
+        }
+        if let Some((_recorder, grid, _path)) = &self.recorder {
+            if self.settings.record.is_none() || !Arc::ptr_eq(grid, &sim.hex_grid) {
+                let (recorder, _grid, path) = self.recorder.take().unwrap();
+                self.errors.push(format!("Recorded {} frames to {}", recorder.finish(), path));
+                if self.settings.record.is_some() {
+                    self.closed_record = self.settings.record.clone();
+                    self.recording_closed = true;
+                }
+            }
+        } else if self.settings.record.is_some() && self.settings.record != self.closed_record {
+            let (path, every, scale, smooth) = self.settings.record.clone().unwrap();
+            let mut recorder: Recorder = Recorder::new(&path, every, scale, smooth);
+            match recorder.record(sim) {
+                Ok(()) => self.recorder = Some((recorder, sim.hex_grid.clone(), path)),
+                Err(error) => {
+                    self.closed_record = self.settings.record.clone();
+                    self.recording_closed = true;
+                    self.errors.push(error);
+                }
+            }
+        }
+        if self.settings.export.is_none() {
+            self.closed_export = None;
+        }
+        if let Some((_series, grid, _path)) = &self.series {
+            if self.settings.export.is_none() || !Arc::ptr_eq(grid, &sim.hex_grid) {
+                let (series, _grid, path) = self.series.take().unwrap();
+                match series.finish() {
+                    Ok(frames) => self.errors.push(format!("Saved {} fields to {}", frames, path)),
+                    Err(error) => self.errors.push(error),
+                }
+                if self.settings.export.is_some() {
+                    self.closed_export = self.settings.export.clone();
+                    self.export_closed = true;
+                }
+            }
+        } else if self.settings.export.is_some() && self.settings.export != self.closed_export {
+            let (path, every) = self.settings.export.clone().unwrap();
+            match FieldSeries::new(&path, every, sim).and_then(|mut series| series.record(sim).map(|_| series)) {
+                Ok(series) => self.series = Some((series, sim.hex_grid.clone(), path)),
+                Err(error) => {
+                    self.closed_export = self.settings.export.clone();
+                    self.export_closed = true;
+                    self.errors.push(error);
+                }
+            }
+        }
+    }
+
+    /// Delivers a copy of the current state to the window
+    ///
+    /// The copy shares everything but the field levels, which are copied into
+    /// the levels of an earlier frame the window has dropped if there is one.
+    fn publish(&mut self) {
+        let sim: &SimulationData = match &self.sim {
+            Some(sim) => sim,
+            None => return,
+        };
+        let mut copy: SimulationData = sim.clone();
+        {
+            let levels = sim.levels.lock();
+            let spare: Option<usize> = self.spare_levels.iter().position(|spare| spare.is_unique() && spare.lock().same_size(&levels));
+            copy.levels = match spare {
+                Some(k) => {
+                    let spare: SharedLevels = self.spare_levels.remove(k);
+                    spare.lock().copy_from(&levels);
+                    spare
+                }
+                None => SharedLevels::new(levels.clone()),
+            };
+        }
+        self.spare_levels.push(copy.levels.clone());
+        if self.spare_levels.len() > SPARE_LEVELS {
+            self.spare_levels.remove(0);
+        }
+        self.edited = false;
+        let frame: Frame = Frame {
+            changes: self.changes,
+            sim: copy,
+            paused: self.paused,
+            recording_closed: self.recording_closed,
+            export_closed: self.export_closed,
+            errors: self.errors.clone(),
+            steps_per_second: self.steps_per_second,
+        };
+        // Fails only once the window is closed and the thread is about to end
+        if self.sink.submit_command(FRAME_READY, frame, None).is_ok() {
+            self.paused = false;
+            self.recording_closed = false;
+            self.export_closed = false;
+            self.errors.clear();
+        }
+    }
+}
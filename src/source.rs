@@ -0,0 +1,103 @@
+use std::f64::consts::PI;
+#[cfg(feature = "gui")]
+use druid::{Data, Lens};
+use crate::cell::CellKind;
+
+/// Signal shapes a source can emit
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "gui", derive(Data))]
+pub enum SourceKind {
+    /// Continuous sine wave
+    Sine,
+    /// Single Ricker wavelet (mexican hat) centered one period after the start
+    Ricker,
+    /// Sine with a frequency rising linearly by the sweep rate
+    Chirp,
+    /// Rectangular pulses with the given duty cycle
+    PulseTrain,
+}
+
+impl SourceKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceKind::Sine => "Sine",
+            SourceKind::Ricker => "Ricker",
+            SourceKind::Chirp => "Chirp",
+            SourceKind::PulseTrain => "Pulses",
+        }
+    }
+}
+
+/// Parses the name of a signal shape, case is ignored
+pub fn parse_source_kind(name: &str) -> Option<SourceKind> {
+    match name.trim().to_lowercase().as_str() {
+        "sine" => Some(SourceKind::Sine),
+        "ricker" => Some(SourceKind::Ricker),
+        "chirp" => Some(SourceKind::Chirp),
+        "pulses" | "pulsetrain" => Some(SourceKind::PulseTrain),
+        _ => None,
+    }
+}
+
+/// A point source injecting a signal into one cell every time step
+#[derive(Clone)]
+#[cfg_attr(feature = "gui", derive(Data, Lens))]
+pub struct Source {
+    pub kind: SourceKind,
+    pub i_x: usize,
+    pub i_y: usize,
+    pub active: bool,
+    pub amplitude: f64,
+    /// Frequency in 1/time unit, start frequency of a chirp
+    pub frequency: f64,
+    /// Phase in radians
+    pub phase: f64,
+    /// Increase of the chirp frequency per time unit
+    pub sweep: f64,
+    /// Fraction of a period a pulse is on
+    pub duty: f64,
+    /// Kind of the cell before the source was placed on it
+    pub cell: CellKind,
+}
+
+impl Source {
+    /// Returns a source on a cell, refused unless its frequency is above 0
+    pub fn new(kind: SourceKind, i_x: usize, i_y: usize, amplitude: f64, frequency: f64) -> Result<Source, String> {
+        // A Ricker wavelet is centred one period after the start, which needs a frequency
+        if frequency <= 0.0 || !frequency.is_finite() {
+            return Err(format!("the frequency of a source has to be above 0, not {}", frequency))
+        }
+        Ok(Source {
+            kind,
+            i_x,
+            i_y,
+            active: true,
+            amplitude,
+            frequency,
+            phase: 0.0,
+            sweep: 0.0,
+            duty: 0.25,
+            cell: CellKind::Interior,
+        })
+    }
+
+    /// Returns the value of the signal at time t
+    pub fn signal(&self, t: f64) -> f64 {
+        match self.kind {
+            SourceKind::Sine => self.amplitude*(2.0*PI*self.frequency*t + self.phase).sin(),
+            SourceKind::Ricker => {
+                let arg: f64 = (PI*self.frequency*(t - 1.0/self.frequency)).powi(2);
+                self.amplitude*(1.0 - 2.0*arg)*(-arg).exp()
+            }
+            SourceKind::Chirp => self.amplitude*(2.0*PI*(self.frequency*t + 0.5*self.sweep*t.powi(2)) + self.phase).sin(),
+            SourceKind::PulseTrain => {
+                let cycle: f64 = (self.frequency*t + self.phase/(2.0*PI)).rem_euclid(1.0);
+                if cycle < self.duty {
+                    self.amplitude
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::f64::consts::PI;
+#[cfg(feature = "gui")]
+use druid::Data;
+
+/// Window applied to a signal before its Fourier transform
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "gui", derive(Data))]
+pub enum Window {
+    /// No window, sharpest peaks with the highest side lobes
+    Rectangular,
+    Hann,
+    Hamming,
+    /// Lowest side lobes with the widest peaks
+    Blackman,
+}
+
+impl Window {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Window::Rectangular => "Rectangular",
+            Window::Hann => "Hann",
+            Window::Hamming => "Hamming",
+            Window::Blackman => "Blackman",
+        }
+    }
+
+    /// Returns the weight of sample i of n
+    pub fn weight(&self, i: usize, n: usize) -> f64 {
+        if n < 2 {
+            return 1.0
+        }
+        let phase: f64 = 2.0*PI*i as f64/(n - 1) as f64;
+        match self {
+            Window::Rectangular => 1.0,
+            Window::Hann => 0.5 - 0.5*phase.cos(),
+            Window::Hamming => 0.54 - 0.46*phase.cos(),
+            Window::Blackman => 0.42 - 0.5*phase.cos() + 0.08*(2.0*phase).cos(),
+        }
+    }
+}
+
+/// Parses the name of a window, case is ignored
+pub fn parse_window(name: &str) -> Option<Window> {
+    match name.trim().to_lowercase().as_str() {
+        "rectangular" | "none" => Some(Window::Rectangular),
+        "hann" | "hanning" => Some(Window::Hann),
+        "hamming" => Some(Window::Hamming),
+        "blackman" => Some(Window::Blackman),
+        _ => None,
+    }
+}
+
+/// Transforms the complex signal given by its real and imaginary parts in
+/// place, the length has to be a power of two
+pub fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n: usize = re.len();
+    // Sorting the samples into bit reversed order
+    let mut j: usize = 0;
+    for i in 1..n {
+        let mut bit: usize = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    // Combining transforms of doubling length
+    let mut len: usize = 2;
+    while len <= n {
+        let angle: f64 = -2.0*PI/len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len/2 {
+                let (w_im, w_re) = (angle*k as f64).sin_cos();
+                let a: usize = start + k;
+                let b: usize = a + len/2;
+                let t_re: f64 = re[b]*w_re - im[b]*w_im;
+                let t_im: f64 = re[b]*w_im + im[b]*w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Single sided amplitude spectrum of a real signal
+pub struct Spectrum {
+    /// Frequency of every bin in 1/time unit
+    pub frequencies: Vec<f64>,
+    /// Amplitude of a sine at the frequency of the bin
+    pub amplitudes: Vec<f64>,
+}
+
+/// Returns the amplitude spectrum of a signal sampled every time_spacing
+///
+/// The mean is removed before windowing, and the signal is padded with zeros
+/// to four times the next power of two, which interpolates the spectrum
+/// between the bins of the signal length.
+pub fn spectrum(values: &[f64], time_spacing: f64, window: Window) -> Spectrum {
+    let n: usize = values.len();
+    let size: usize = n.next_power_of_two()*4;
+    let mean: f64 = values.iter().sum::<f64>()/n.max(1) as f64;
+    let mut re: Vec<f64> = vec![0.0; size];
+    let mut im: Vec<f64> = vec![0.0; size];
+    let mut weight_sum: f64 = 0.0;
+    for (i, value) in values.iter().enumerate() {
+        let weight: f64 = window.weight(i, n);
+        re[i] = (value - mean)*weight;
+        weight_sum += weight;
+    }
+    fft(&mut re, &mut im);
+    // Scaling by the sum of the weights gives the amplitude of a sine on its bin
+    let scale: f64 = if weight_sum > 0.0 {2.0/weight_sum} else {0.0};
+    let bins: usize = size/2 + 1;
+    Spectrum {
+        frequencies: (0..bins).map(|k| k as f64/(size as f64*time_spacing)).collect(),
+        amplitudes: (0..bins).map(|k| (re[k].powi(2) + im[k].powi(2)).sqrt()*scale).collect(),
+    }
+}
+
+/// Returns up to count peaks of a spectrum as frequency and amplitude, the
+/// strongest first
+///
+/// Peaks are local maxima above a thousandth of the largest amplitude, their
+/// frequency is refined with a parabola through the neighbouring bins. Bins
+/// that are not finite, as recorded by a diverged run, are never peaks.
+pub fn find_peaks(spectrum: &Spectrum, count: usize) -> Vec<(f64, f64)> {
+    let amplitudes: &Vec<f64> = &spectrum.amplitudes;
+    let max: f64 = amplitudes.iter().cloned().fold(0.0, f64::max);
+    let mut peaks: Vec<(f64, f64)> = Vec::new();
+    if amplitudes.len() < 3 {
+        return peaks
+    }
+    let bin_width: f64 = spectrum.frequencies[1] - spectrum.frequencies[0];
+    for k in 1..amplitudes.len() - 1 {
+        let (left, centre, right) = (amplitudes[k - 1], amplitudes[k], amplitudes[k + 1]);
+        if centre.is_finite() && centre > left && centre >= right && centre > 1e-3*max {
+            let curvature: f64 = left - 2.0*centre + right;
+            let offset: f64 = if curvature < 0.0 {0.5*(left - right)/curvature} else {0.0};
+            peaks.push((spectrum.frequencies[k] + offset*bin_width, centre - 0.25*(left - right)*offset));
+        }
+    }
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+    peaks.truncate(count);
+    peaks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transforms_like_the_discrete_fourier_transform() {
+        let n: usize = 16;
+        let signal: Vec<f64> = (0..n).map(|i| (0.3*i as f64).sin() + 0.1*(i%3) as f64).collect();
+        let mut re: Vec<f64> = signal.clone();
+        let mut im: Vec<f64> = vec![0.0; n];
+        fft(&mut re, &mut im);
+        for k in 0..n {
+            let (mut sum_re, mut sum_im) = (0.0, 0.0);
+            for (i, value) in signal.iter().enumerate() {
+                let angle: f64 = -2.0*PI*(k*i) as f64/n as f64;
+                sum_re += value*angle.cos();
+                sum_im += value*angle.sin();
+            }
+            assert!((re[k] - sum_re).abs() < 1e-9 && (im[k] - sum_im).abs() < 1e-9, "bin {}", k);
+        }
+    }
+
+    #[test]
+    fn finds_the_peaks_of_two_sines() {
+        // Off the bins, so the parabola has to refine the frequency
+        let time_spacing: f64 = 0.01;
+        let values: Vec<f64> = (0..3000).map(|i| {
+            let t: f64 = i as f64*time_spacing;
+            1.0 + 2.0*(2.0*PI*3.3*t).sin() + 0.5*(2.0*PI*11.7*t + 0.4).sin()
+        }).collect();
+        for &window in [Window::Hann, Window::Hamming, Window::Blackman].iter() {
+            let peaks: Vec<(f64, f64)> = find_peaks(&spectrum(&values, time_spacing, window), 2);
+            assert_eq!(peaks.len(), 2);
+            assert!((peaks[0].0 - 3.3).abs() < 0.01 && (peaks[0].1 - 2.0).abs() < 0.05, "{} at {}", peaks[0].1, peaks[0].0);
+            assert!((peaks[1].0 - 11.7).abs() < 0.01 && (peaks[1].1 - 0.5).abs() < 0.05, "{} at {}", peaks[1].1, peaks[1].0);
+        }
+    }
+
+    #[test]
+    fn skips_a_diverged_recording() {
+        let values: Vec<f64> = (0..64).map(|i| if i < 40 {(0.5*i as f64).sin()} else {f64::NAN}).collect();
+        assert!(find_peaks(&spectrum(&values, 0.01, Window::Hann), 4).is_empty());
+        let spectrum: Spectrum = Spectrum{frequencies: vec![0.0, 1.0, 2.0, 3.0, 4.0], amplitudes: vec![0.0, f64::INFINITY, 0.0, 1.0, f64::NAN]};
+        assert!(find_peaks(&spectrum, 4).is_empty());
+    }
+
+    #[test]
+    fn parses_window_names() {
+        assert!(parse_window(" Hanning") == Some(Window::Hann));
+        assert!(parse_window("none") == Some(Window::Rectangular));
+        assert!(parse_window("triangle").is_none());
+    }
+}